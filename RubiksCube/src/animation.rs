@@ -0,0 +1,272 @@
+use std::fmt::Write;
+use std::path::Path;
+
+use crate::notation::{Algorithm, Move};
+use crate::render::{Image, PartialTurn, RenderOptions};
+use crate::svg::{hex, number};
+use crate::{CubeError, RubiksCube, TurnDirection};
+
+// Playing an algorithm back move by move for tutorials
+// Each move is drawn over several frames with its layer turning a little further every frame, the
+// same turn CubeView makes with rotate_front, rotate_top or rotate_right all at once. The frames
+// can be saved as an animated SVG that loops by itself, or as numbered images to put together
+// with a video tool
+
+// How the labels are drawn on images, a 5 by 7 pixel font for the characters move notation uses
+// Each row is 5 bits, with the highest bit the leftmost pixel
+const GLYPH_WIDTH: usize = 5;
+const GLYPH_HEIGHT: usize = 7;
+const GLYPHS: [(char, [u8; GLYPH_HEIGHT]); 17] = [
+    ('0', [0x0E, 0x11, 0x13, 0x15, 0x19, 0x11, 0x0E]),
+    ('1', [0x04, 0x0C, 0x04, 0x04, 0x04, 0x04, 0x0E]),
+    ('2', [0x0E, 0x11, 0x01, 0x02, 0x04, 0x08, 0x1F]),
+    ('3', [0x1F, 0x02, 0x04, 0x02, 0x01, 0x11, 0x0E]),
+    ('4', [0x02, 0x06, 0x0A, 0x12, 0x1F, 0x02, 0x02]),
+    ('5', [0x1F, 0x10, 0x1E, 0x01, 0x01, 0x11, 0x0E]),
+    ('6', [0x06, 0x08, 0x10, 0x1E, 0x11, 0x11, 0x0E]),
+    ('7', [0x1F, 0x01, 0x02, 0x04, 0x08, 0x08, 0x08]),
+    ('8', [0x0E, 0x11, 0x11, 0x0E, 0x11, 0x11, 0x0E]),
+    ('9', [0x0E, 0x11, 0x11, 0x0F, 0x01, 0x02, 0x0C]),
+    ('U', [0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0E]),
+    ('D', [0x1E, 0x11, 0x11, 0x11, 0x11, 0x11, 0x1E]),
+    ('L', [0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x1F]),
+    ('R', [0x1E, 0x11, 0x11, 0x1E, 0x14, 0x12, 0x11]),
+    ('F', [0x1F, 0x10, 0x10, 0x1E, 0x10, 0x10, 0x10]),
+    ('B', [0x1E, 0x11, 0x11, 0x1E, 0x11, 0x11, 0x1E]),
+    ('\'', [0x04, 0x04, 0x08, 0x00, 0x00, 0x00, 0x00]),
+];
+
+// How tall labels are, as a share of the picture's height
+const LABEL_HEIGHT: f64 = 0.08;
+
+// The fewest digits frame numbers are written with, so the files sort in order
+const FRAME_DIGITS: usize = 4;
+
+#[derive(Clone, Debug)]
+pub(crate) struct Animation {
+    pub(crate) frames_per_move: usize, // Frames drawn for each quarter or half turn
+    pub(crate) seconds_per_move: f64,  // How long each move takes in the animated SVG
+    pub(crate) labels: bool,           // Write each move under the cube while it is being made
+    pub(crate) label_color: [u8; 3],
+    pub(crate) render: RenderOptions, // How the cube is drawn, and the size of each frame
+}
+
+impl Default for Animation {
+    fn default() -> Self {
+        Animation {
+            frames_per_move: 10,
+            seconds_per_move: 0.6,
+            labels: true,
+            label_color: [0, 0, 0],
+            render: RenderOptions {
+                width: 256,
+                height: 256,
+                ..RenderOptions::default()
+            },
+        }
+    }
+}
+
+// One picture of the animation
+#[derive(Clone, Debug)]
+pub(crate) struct Frame {
+    pub(crate) cube: RubiksCube, // The cube before the move being made
+    pub(crate) turn: Option<PartialTurn>, // How far into the move the frame is
+    pub(crate) label: String,    // The move being made, empty once it's all done
+}
+
+impl Animation {
+    // Every frame of the algorithm played on the cube, ending with a frame of the finished cube
+    pub(crate) fn frames(
+        &self,
+        cube: &RubiksCube,
+        algorithm: &Algorithm,
+    ) -> Result<Vec<Frame>, CubeError> {
+        let frames_per_move = self.frames_per_move.max(1);
+        let mut cube = cube.clone();
+        let mut frames = vec![];
+        for m in &algorithm.moves {
+            let next = cube.try_do_algorithm(&Algorithm { moves: vec![*m] })?;
+            let quarter_turns = quarter_turns(m);
+            for i in 0..frames_per_move {
+                // Easing in and out, so the layer starts and stops turning smoothly
+                let t = i as f64 / frames_per_move as f64;
+                let eased = t * t * (3.0 - 2.0 * t);
+                frames.push(Frame {
+                    cube: cube.clone(),
+                    turn: Some(PartialTurn {
+                        face: m.face,
+                        layer: m.layer,
+                        quarter_turns: quarter_turns * eased,
+                    }),
+                    label: m.to_string(),
+                });
+            }
+            cube = next;
+        }
+        frames.push(Frame {
+            cube,
+            turn: None,
+            label: String::new(),
+        });
+        Ok(frames)
+    }
+
+    // Draws a frame, with its label under the cube
+    pub(crate) fn render_frame(&self, frame: &Frame) -> Image {
+        let mut image = frame.cube.render_turning(&self.render, frame.turn);
+        if self.labels {
+            draw_label(&mut image, &frame.label, self.label_color);
+        }
+        image
+    }
+
+    // Renders every frame to a numbered image in dir, named like frame_0001.png, making dir if it
+    // isn't there yet
+    // The extension picks the format the same way Image::save does, and the number of frames
+    // written is returned
+    pub(crate) fn save_frames(
+        &self,
+        cube: &RubiksCube,
+        algorithm: &Algorithm,
+        dir: impl AsRef<Path>,
+        extension: &str,
+    ) -> Result<usize, CubeError> {
+        let frames = self.frames(cube, algorithm)?;
+        let dir = dir.as_ref();
+        std::fs::create_dir_all(dir)?;
+        let digits = frames.len().to_string().len().max(FRAME_DIGITS);
+        for (i, frame) in frames.iter().enumerate() {
+            let name = format!("frame_{:0digits$}.{extension}", i + 1);
+            self.render_frame(frame).save(dir.join(name))?;
+        }
+        Ok(frames.len())
+    }
+
+    // An SVG that plays the frames over and over
+    // Every frame is a group of polygons drawn back to front, shown only for its turn by a
+    // discrete animation of its display, and the finished cube stays up for as long as a move
+    // takes before starting again
+    pub(crate) fn to_svg(
+        &self,
+        cube: &RubiksCube,
+        algorithm: &Algorithm,
+    ) -> Result<String, CubeError> {
+        let frames = self.frames(cube, algorithm)?;
+        let (width, height) = (self.render.width, self.render.height);
+        let frame_seconds = self.seconds_per_move / self.frames_per_move.max(1) as f64;
+        let last = frames.len() - 1;
+        let total = frame_seconds * last as f64 + self.seconds_per_move;
+        let mut svg = String::new();
+        // Writing to a String can't fail
+        let _ = writeln!(
+            svg,
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="{width}" height="{height}" viewBox="0 0 {width} {height}">"#
+        );
+        let _ = writeln!(
+            svg,
+            r#"<rect width="{width}" height="{height}" fill="{}"/>"#,
+            hex(self.render.background)
+        );
+        for (i, frame) in frames.iter().enumerate() {
+            // When the frame comes up and goes away, as a share of the whole animation
+            let (mut values, mut times) = (vec![], vec![]);
+            if i > 0 {
+                values.push("none");
+                times.push(0.0);
+            }
+            values.push("inline");
+            times.push(frame_seconds * i as f64 / total);
+            if i < last {
+                values.push("none");
+                times.push(frame_seconds * (i + 1) as f64 / total);
+            }
+            let times: Vec<String> = times.iter().map(|t| format!("{t:.6}")).collect();
+            let _ = writeln!(
+                svg,
+                r#"<g display="{}" stroke-width="0.5" stroke-linejoin="round">"#,
+                if i == 0 { "inline" } else { "none" }
+            );
+            let _ = writeln!(
+                svg,
+                r#"<animate attributeName="display" values="{}" keyTimes="{}" dur="{}s" calcMode="discrete" repeatCount="indefinite"/>"#,
+                values.join(";"),
+                times.join(";"),
+                number(total)
+            );
+            // Drawing the farthest quads first leaves the nearest on top, the stickers being
+            // lifted off the body keeps them in front of the body behind them
+            let mut quads = frame
+                .cube
+                .projected_quads(&self.render, frame.turn, width, height);
+            let nearness = |corners: &[[f64; 3]; 4]| corners.iter().map(|c| c[2]).sum::<f64>();
+            quads.sort_by(|a, b| nearness(&a.0).total_cmp(&nearness(&b.0)));
+            for (corners, color) in quads {
+                let points: Vec<String> = corners
+                    .iter()
+                    .map(|[x, y, _]| format!("{},{}", number(*x), number(*y)))
+                    .collect();
+                // The outline in the same color covers the hairline gaps between neighbors
+                let _ = writeln!(
+                    svg,
+                    r#"<polygon points="{}" fill="{}" stroke="{}"/>"#,
+                    points.join(" "),
+                    hex(color),
+                    hex(color)
+                );
+            }
+            if self.labels && !frame.label.is_empty() {
+                let size = LABEL_HEIGHT * height as f64;
+                let _ = writeln!(
+                    svg,
+                    r#"<text x="{}" y="{}" font-family="sans-serif" font-size="{}" text-anchor="middle" fill="{}">{}</text>"#,
+                    number(width as f64 / 2.0),
+                    number(height as f64 - size / 2.0),
+                    number(size),
+                    hex(self.label_color),
+                    frame.label
+                );
+            }
+            svg.push_str("</g>\n");
+        }
+        svg.push_str("</svg>\n");
+        Ok(svg)
+    }
+}
+
+// How far a move turns its layer, clockwise as the face is looked at
+fn quarter_turns(m: &Move) -> f64 {
+    let turns = if m.double { 2.0 } else { 1.0 };
+    match m.direction {
+        TurnDirection::CLOCKWISE => turns,
+        TurnDirection::COUNTERCLOCKWISE => -turns,
+    }
+}
+
+// Writes a label centered along the bottom of an image in the built in font
+// Characters the font doesn't have are left as spaces
+fn draw_label(image: &mut Image, label: &str, color: [u8; 3]) {
+    let scale = ((LABEL_HEIGHT * image.height() as f64) as usize / GLYPH_HEIGHT).max(1);
+    let advance = (GLYPH_WIDTH + 1) * scale;
+    let width = (label.chars().count() * advance).saturating_sub(scale);
+    let left = image.width().saturating_sub(width) / 2;
+    let top = image.height().saturating_sub((GLYPH_HEIGHT + 1) * scale);
+    for (i, c) in label.chars().enumerate() {
+        let Some((_, rows)) = GLYPHS.iter().find(|(glyph, _)| *glyph == c) else {
+            continue;
+        };
+        for (row, bits) in rows.iter().enumerate() {
+            for column in 0..GLYPH_WIDTH {
+                if bits >> (GLYPH_WIDTH - 1 - column) & 1 == 0 {
+                    continue;
+                }
+                for dy in 0..scale {
+                    for dx in 0..scale {
+                        let x = left + i * advance + column * scale + dx;
+                        image.set_pixel(x, top + row * scale + dy, color);
+                    }
+                }
+            }
+        }
+    }
+}
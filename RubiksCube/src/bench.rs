@@ -0,0 +1,116 @@
+use std::hint::black_box;
+use std::time::Instant;
+
+use crate::move_table::{MoveTable, MAX_TABLE_SIZE};
+use crate::{CubeError, RubiksColor, RubiksCube, TurnDirection};
+
+// Code for timing how fast the cube can be turned
+// Run with `cargo run --release -- bench [sizes...]`
+
+pub(crate) const DEFAULT_SIZES: [usize; 4] = [3, 10, 33, 100];
+
+// Roughly how many stickers each timing run should move, so big cubes don't take forever
+const STICKERS_PER_RUN: usize = 50_000_000;
+
+// Times the same moves done with do_move, which clones the cube and turns the copy, with
+// apply_move, which turns it in place, and by looking them up in a move table
+// Cubes too big for a move table leave the last two columns empty
+// Every column runs the current turning code, so the ratios only show what cloning costs and what
+// the table saves, not how any older version of the cube compared
+pub(crate) fn run(args: &[String]) -> Result<(), CubeError> {
+    let sizes = if args.is_empty() {
+        DEFAULT_SIZES.to_vec()
+    } else {
+        args.iter()
+            .map(|arg| {
+                arg.parse::<usize>()
+                    .map_err(|_| CubeError::Parse(format!("\"{arg}\" as a cube size")))
+            })
+            .collect::<Result<Vec<_>, _>>()?
+    };
+    println!(
+        "{:>6} {:>10} {:>18} {:>18} {:>8} {:>18} {:>8}",
+        "size", "moves", "clone+turn/s", "apply_move/s", "ratio", "move_table/s", "ratio"
+    );
+    for size in sizes {
+        let mut cube = RubiksCube::try_new(size)?;
+        // Each move touches about one face worth of stickers
+        let count = (STICKERS_PER_RUN / (size * size + 4 * size)).max(1000);
+
+        let mut moves = MoveSequence::new(size);
+        let start = Instant::now();
+        for _ in 0..count {
+            let (face, layer, direction) = moves.next_move();
+            cube = cube.do_move(face, layer, direction);
+        }
+        black_box(&cube);
+        let cloned = count as f64 / start.elapsed().as_secs_f64();
+
+        let mut moves = MoveSequence::new(size);
+        let start = Instant::now();
+        for _ in 0..count {
+            let (face, layer, direction) = moves.next_move();
+            cube.apply_move(face, layer, direction);
+        }
+        black_box(&cube);
+        let in_place = count as f64 / start.elapsed().as_secs_f64();
+
+        // Building the table isn't counted, it's meant to be done once up front
+        let looked_up = match table(size)? {
+            Some(table) => {
+                let mut moves = MoveSequence::new(size);
+                let start = Instant::now();
+                for _ in 0..count {
+                    let (face, layer, direction) = moves.next_move();
+                    table.try_apply_move(&mut cube, face, layer, direction)?;
+                }
+                black_box(&cube);
+                let looked_up = count as f64 / start.elapsed().as_secs_f64();
+                format!("{looked_up:>18.0} {:>7.1}x", looked_up / cloned)
+            }
+            None => format!("{:>18} {:>8}", "-", "-"),
+        };
+
+        println!(
+            "{size:>6} {count:>10} {cloned:>18.0} {in_place:>18.0} {:>7.1}x {looked_up}",
+            in_place / cloned
+        );
+    }
+    Ok(())
+}
+
+// The move table to time moves on, if the cube isn't too big to have one
+pub(crate) fn table(size: usize) -> Result<Option<MoveTable>, CubeError> {
+    if size > MAX_TABLE_SIZE {
+        return Ok(None);
+    }
+    MoveTable::new(size).map(Some)
+}
+
+// Picks the same moves every run so every way of turning the cube does identical work
+struct MoveSequence {
+    state: u64,
+    size: usize,
+}
+
+impl MoveSequence {
+    fn new(size: usize) -> Self {
+        Self { state: 1, size }
+    }
+
+    fn next_move(&mut self) -> (RubiksColor, usize, TurnDirection) {
+        self.state = self
+            .state
+            .wrapping_mul(6364136223846793005)
+            .wrapping_add(1442695040888963407);
+        let bits = self.state >> 16;
+        let face = RubiksColor::ALL[(bits % 6) as usize];
+        let layer = ((bits >> 8) % self.size as u64) as usize;
+        let direction = if bits >> 40 & 1 == 0 {
+            TurnDirection::CLOCKWISE
+        } else {
+            TurnDirection::COUNTERCLOCKWISE
+        };
+        (face, layer, direction)
+    }
+}
@@ -0,0 +1,455 @@
+use crate::notation::{Algorithm, Move};
+use crate::{CubeError, RubiksColor, RubiksCube, TurnDirection};
+
+// Code for describing a 3x3 by numbers instead of stickers
+// Searches index their tables by coordinates, small numbers that each capture one part of the
+// cube's state, like how the corners are twisted. A coordinate can be moved with a table lookup,
+// which is much faster than turning stickers around
+
+// Ranking turns an arrangement into its position in a fixed list of every arrangement of that
+// kind, and unranking goes back the other way
+
+// Ranks a permutation of 0..n by its Lehmer code, which puts permutations in lexicographic order
+// so the identity is 0 and the reversed list is n! - 1
+pub(crate) fn rank_permutation(permutation: &[usize]) -> usize {
+    let mut rank = 0;
+    for (i, value) in permutation.iter().enumerate() {
+        let smaller_after = permutation[i + 1..].iter().filter(|v| *v < value).count();
+        rank = rank * (permutation.len() - i) + smaller_after;
+    }
+    rank
+}
+
+pub(crate) fn unrank_permutation(mut rank: usize, length: usize) -> Vec<usize> {
+    let mut unused: Vec<usize> = (0..length).collect();
+    let mut permutation = Vec::with_capacity(length);
+    for i in 0..length {
+        let place_value = factorial(length - 1 - i);
+        permutation.push(unused.remove(rank / place_value));
+        rank %= place_value;
+    }
+    permutation
+}
+
+// Ranks a list of twists where the whole list always adds up to a multiple of the base, like the
+// corner twists of a real cube, so the last one is left out since the others decide it
+pub(crate) fn rank_orientation(orientation: &[u8], base: u8) -> usize {
+    orientation[..orientation.len() - 1]
+        .iter()
+        .fold(0, |rank, twist| rank * base as usize + *twist as usize)
+}
+
+pub(crate) fn unrank_orientation(mut rank: usize, length: usize, base: u8) -> Vec<u8> {
+    let mut orientation = vec![0; length];
+    for twist in orientation[..length - 1].iter_mut().rev() {
+        *twist = (rank % base as usize) as u8;
+        rank /= base as usize;
+    }
+    let total: usize = orientation.iter().map(|twist| *twist as usize).sum();
+    orientation[length - 1] = ((base as usize - total % base as usize) % base as usize) as u8;
+    orientation
+}
+
+// Ranks which spots out of a list are picked, giving every way of picking the same number of
+// spots a different rank from 0 up to (length choose picked)
+// Spots are counted from the end of the list, so picking the last few spots ranks as 0
+pub(crate) fn rank_combination(picked: &[bool]) -> usize {
+    let mut rank = 0;
+    let mut seen = 0;
+    for (i, is_picked) in picked.iter().rev().enumerate() {
+        if *is_picked {
+            seen += 1;
+            rank += binomial(i, seen);
+        }
+    }
+    rank
+}
+
+pub(crate) fn unrank_combination(mut rank: usize, length: usize, mut count: usize) -> Vec<bool> {
+    let mut picked = vec![false; length];
+    for i in (0..length).rev() {
+        if count > 0 && binomial(i, count) <= rank {
+            picked[length - 1 - i] = true;
+            rank -= binomial(i, count);
+            count -= 1;
+        }
+    }
+    picked
+}
+
+fn factorial(n: usize) -> usize {
+    (1..=n).product()
+}
+
+fn binomial(n: usize, k: usize) -> usize {
+    if k > n {
+        return 0;
+    }
+    (0..k).fold(1, |result, i| result * (n - i) / (i + 1))
+}
+
+// Which stickers make up each corner and edge slot of a 3x3, as (face, x, y)
+// Corners list their up or down sticker first and then go clockwise around the corner, edges list
+// their up or down sticker first, or their front or back one for the middle layer
+// The slots are in the order most solvers use: URF UFL ULB UBR DFR DLF DBL DRB for corners and
+// UR UF UL UB DR DF DL DB FR FL BL BR for edges
+const CORNER_STICKERS: [[(RubiksColor, usize, usize); 3]; 8] = {
+    use RubiksColor::*;
+    [
+        [(UP, 2, 2), (RIGHT, 0, 0), (FORWARD, 2, 0)],
+        [(UP, 0, 2), (FORWARD, 0, 0), (LEFT, 2, 0)],
+        [(UP, 0, 0), (LEFT, 0, 0), (BACK, 2, 0)],
+        [(UP, 2, 0), (BACK, 0, 0), (RIGHT, 2, 0)],
+        [(DOWN, 2, 0), (FORWARD, 2, 2), (RIGHT, 0, 2)],
+        [(DOWN, 0, 0), (LEFT, 2, 2), (FORWARD, 0, 2)],
+        [(DOWN, 0, 2), (BACK, 2, 2), (LEFT, 0, 2)],
+        [(DOWN, 2, 2), (RIGHT, 2, 2), (BACK, 0, 2)],
+    ]
+};
+
+const EDGE_STICKERS: [[(RubiksColor, usize, usize); 2]; 12] = {
+    use RubiksColor::*;
+    [
+        [(UP, 2, 1), (RIGHT, 1, 0)],
+        [(UP, 1, 2), (FORWARD, 1, 0)],
+        [(UP, 0, 1), (LEFT, 1, 0)],
+        [(UP, 1, 0), (BACK, 1, 0)],
+        [(DOWN, 2, 1), (RIGHT, 1, 2)],
+        [(DOWN, 1, 0), (FORWARD, 1, 2)],
+        [(DOWN, 0, 1), (LEFT, 1, 2)],
+        [(DOWN, 1, 2), (BACK, 1, 2)],
+        [(FORWARD, 2, 1), (RIGHT, 0, 1)],
+        [(FORWARD, 0, 1), (LEFT, 2, 1)],
+        [(BACK, 2, 1), (LEFT, 0, 1)],
+        [(BACK, 0, 1), (RIGHT, 2, 1)],
+    ]
+};
+
+// The edges that belong in the middle layer between up and down
+const SLICE_EDGES: [u8; 4] = [8, 9, 10, 11];
+
+// A 3x3 described by where its corners and edges are and how they are turned
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
+pub(crate) struct CubieCube {
+    pub(crate) corner_permutation: [u8; 8], // Which corner is sitting in each corner slot
+    pub(crate) corner_orientation: [u8; 8], // How many times each corner is twisted clockwise
+    pub(crate) edge_permutation: [u8; 12],  // Which edge is sitting in each edge slot
+    pub(crate) edge_orientation: [u8; 12],  // Whether each edge is flipped
+}
+
+impl CubieCube {
+    pub(crate) const SOLVED: CubieCube = CubieCube {
+        corner_permutation: [0, 1, 2, 3, 4, 5, 6, 7],
+        corner_orientation: [0; 8],
+        edge_permutation: [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11],
+        edge_orientation: [0; 12],
+    };
+
+    // Works out the pieces of a 3x3 from its stickers
+    // The centers are taken to be where they belong, so the cube shouldn't have had its middle
+    // layers turned
+    pub(crate) fn from_cube(cube: &RubiksCube) -> Result<Self, CubeError> {
+        if cube.face_size != 3 {
+            return Err(CubeError::WrongSize {
+                face_size: cube.face_size,
+                expected: 3,
+            });
+        }
+        let color = |(face, x, y): (RubiksColor, usize, usize)| {
+            cube.stickers[cube.sticker_index(face, x, y)]
+        };
+        let mut cubies = CubieCube::SOLVED;
+        // Step 1: Find each corner by its colors, and how far round its up or down color is
+        for (slot, stickers) in CORNER_STICKERS.iter().enumerate() {
+            let colors = stickers.map(color);
+            let twist = (0..3)
+                .find(|i| matches!(colors[*i], RubiksColor::UP | RubiksColor::DOWN))
+                .ok_or_else(|| corner_error(slot))?;
+            let corner = (0..8)
+                .find(|corner| {
+                    (0..3).all(|i| CORNER_STICKERS[*corner][i].0 == colors[(i + twist) % 3])
+                })
+                .ok_or_else(|| corner_error(slot))?;
+            cubies.corner_permutation[slot] = corner as u8;
+            cubies.corner_orientation[slot] = twist as u8;
+        }
+        // Step 2: Find each edge by its colors, and whether they are the other way round
+        for (slot, stickers) in EDGE_STICKERS.iter().enumerate() {
+            let colors = stickers.map(color);
+            let (edge, flip) = (0..12)
+                .flat_map(|edge| [(edge, 0), (edge, 1)])
+                .find(|(edge, flip)| {
+                    (0..2).all(|i| EDGE_STICKERS[*edge][i].0 == colors[(i + flip) % 2])
+                })
+                .ok_or_else(|| {
+                    CubeError::InvalidState(format!("edge slot {slot} has no matching edge"))
+                })?;
+            cubies.edge_permutation[slot] = edge as u8;
+            cubies.edge_orientation[slot] = flip as u8;
+        }
+        Ok(cubies)
+    }
+
+    // Doing this state and then the other one
+    // Each slot ends up with the piece the other state pulls into it from this state, turned by
+    // however much both states turn it
+    pub(crate) fn then(&self, other: &CubieCube) -> CubieCube {
+        let mut result = CubieCube::SOLVED;
+        for slot in 0..8 {
+            let from = other.corner_permutation[slot] as usize;
+            result.corner_permutation[slot] = self.corner_permutation[from];
+            result.corner_orientation[slot] =
+                (self.corner_orientation[from] + other.corner_orientation[slot]) % 3;
+        }
+        for slot in 0..12 {
+            let from = other.edge_permutation[slot] as usize;
+            result.edge_permutation[slot] = self.edge_permutation[from];
+            result.edge_orientation[slot] =
+                (self.edge_orientation[from] + other.edge_orientation[slot]) % 2;
+        }
+        result
+    }
+
+    // What a single move does to the pieces of a solved cube
+    // Only whole face turns are allowed, since turning a middle layer would move the centers
+    pub(crate) fn of_move(m: &Move) -> Result<CubieCube, CubeError> {
+        if m.layer != 0 {
+            return Err(CubeError::InvalidState(format!(
+                "{m} turns a middle layer, which moves the centers"
+            )));
+        }
+        let algorithm = Algorithm { moves: vec![*m] };
+        CubieCube::from_cube(&RubiksCube::new(3).try_do_algorithm(&algorithm)?)
+    }
+}
+
+fn corner_error(slot: usize) -> CubeError {
+    CubeError::InvalidState(format!("corner slot {slot} has no matching corner"))
+}
+
+// The 18 turns of the outer faces, each face clockwise, counterclockwise and twice
+pub(crate) fn face_moves() -> Vec<Move> {
+    let mut moves = vec![];
+    for face in RubiksColor::ALL {
+        moves.push(Move::new(face, 0, TurnDirection::CLOCKWISE));
+        moves.push(Move::new(face, 0, TurnDirection::COUNTERCLOCKWISE));
+        moves.push(Move {
+            double: true,
+            ..Move::new(face, 0, TurnDirection::CLOCKWISE)
+        });
+    }
+    moves
+}
+
+// The face moves that keep a cube with its pieces oriented and its middle layer edges in the
+// middle layer that way, the up and down faces turned any way and the rest only turned twice
+pub(crate) fn phase_two_moves() -> Vec<Move> {
+    face_moves()
+        .into_iter()
+        .filter(|m| matches!(m.face, RubiksColor::UP | RubiksColor::DOWN) || m.double)
+        .collect()
+}
+
+// The coordinates solvers usually build their tables from
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
+pub(crate) enum Coordinate {
+    CornerOrientation, // How the corners are twisted, 3^7 values
+    EdgeOrientation,   // How the edges are flipped, 2^11 values
+    CornerPermutation, // Where the corners are, 8! values
+    EdgePermutation,   // Where the edges are, 12! values
+    UdSlice,           // Which edge slots hold the middle layer edges, 12 choose 4 values
+    // These two only mean anything while the middle layer edges are in the middle layer
+    UdEdgePermutation, // Where the up and down layer edges are, 8! values
+    SlicePermutation,  // Where the middle layer edges are, 4! values
+}
+
+impl Coordinate {
+    pub(crate) const ALL: [Coordinate; 7] = [
+        Coordinate::CornerOrientation,
+        Coordinate::EdgeOrientation,
+        Coordinate::CornerPermutation,
+        Coordinate::EdgePermutation,
+        Coordinate::UdSlice,
+        Coordinate::UdEdgePermutation,
+        Coordinate::SlicePermutation,
+    ];
+
+    // A number for the coordinate that stays the same from build to build, for saving to files
+    pub(crate) fn id(&self) -> u8 {
+        Coordinate::ALL.iter().position(|c| c == self).unwrap() as u8
+    }
+
+    pub(crate) fn from_id(id: u8) -> Option<Coordinate> {
+        Coordinate::ALL.get(id as usize).copied()
+    }
+
+    // How many values the coordinate can take, a solved cube is always at 0
+    pub(crate) fn size(&self) -> usize {
+        match self {
+            Coordinate::CornerOrientation => 3usize.pow(7),
+            Coordinate::EdgeOrientation => 2usize.pow(11),
+            Coordinate::CornerPermutation => factorial(8),
+            Coordinate::EdgePermutation => factorial(12),
+            Coordinate::UdSlice => binomial(12, 4),
+            Coordinate::UdEdgePermutation => factorial(8),
+            Coordinate::SlicePermutation => factorial(4),
+        }
+    }
+
+    pub(crate) fn get(&self, cubies: &CubieCube) -> usize {
+        match self {
+            Coordinate::CornerOrientation => rank_orientation(&cubies.corner_orientation, 3),
+            Coordinate::EdgeOrientation => rank_orientation(&cubies.edge_orientation, 2),
+            Coordinate::CornerPermutation => {
+                rank_permutation(&cubies.corner_permutation.map(usize::from))
+            }
+            Coordinate::EdgePermutation => {
+                rank_permutation(&cubies.edge_permutation.map(usize::from))
+            }
+            Coordinate::UdSlice => rank_combination(
+                &cubies
+                    .edge_permutation
+                    .map(|edge| SLICE_EDGES.contains(&edge)),
+            ),
+            Coordinate::UdEdgePermutation => {
+                let edges: Vec<usize> = cubies.edge_permutation[..8]
+                    .iter()
+                    .map(|e| *e as usize)
+                    .collect();
+                rank_permutation(&edges)
+            }
+            Coordinate::SlicePermutation => {
+                let edges: Vec<usize> = cubies.edge_permutation[8..]
+                    .iter()
+                    .map(|e| (*e as usize).saturating_sub(8))
+                    .collect();
+                rank_permutation(&edges)
+            }
+        }
+    }
+
+    // The face moves the coordinate keeps its meaning under
+    pub(crate) fn moves(&self) -> Vec<Move> {
+        match self {
+            Coordinate::UdEdgePermutation | Coordinate::SlicePermutation => phase_two_moves(),
+            _ => face_moves(),
+        }
+    }
+
+    // Builds a state with the given value of this coordinate and everything else left solved
+    // as far as it can be
+    pub(crate) fn cubies_at(&self, value: usize) -> CubieCube {
+        let mut cubies = CubieCube::SOLVED;
+        match self {
+            Coordinate::CornerOrientation => {
+                let orientation = unrank_orientation(value, 8, 3);
+                cubies.corner_orientation.copy_from_slice(&orientation);
+            }
+            Coordinate::EdgeOrientation => {
+                let orientation = unrank_orientation(value, 12, 2);
+                cubies.edge_orientation.copy_from_slice(&orientation);
+            }
+            Coordinate::CornerPermutation => {
+                for (slot, corner) in unrank_permutation(value, 8).into_iter().enumerate() {
+                    cubies.corner_permutation[slot] = corner as u8;
+                }
+            }
+            Coordinate::EdgePermutation => {
+                for (slot, edge) in unrank_permutation(value, 12).into_iter().enumerate() {
+                    cubies.edge_permutation[slot] = edge as u8;
+                }
+            }
+            Coordinate::UdSlice => {
+                // The slice edges fill the picked slots in order and the rest fill the others
+                let picked = unrank_combination(value, 12, SLICE_EDGES.len());
+                let mut slice_edges = SLICE_EDGES.into_iter();
+                let mut other_edges = (0..12).filter(|edge| !SLICE_EDGES.contains(edge));
+                for (slot, is_slice) in picked.into_iter().enumerate() {
+                    let next = if is_slice {
+                        slice_edges.next()
+                    } else {
+                        other_edges.next()
+                    };
+                    cubies.edge_permutation[slot] = next.unwrap();
+                }
+            }
+            Coordinate::UdEdgePermutation => {
+                for (slot, edge) in unrank_permutation(value, 8).into_iter().enumerate() {
+                    cubies.edge_permutation[slot] = edge as u8;
+                }
+            }
+            Coordinate::SlicePermutation => {
+                for (slot, edge) in unrank_permutation(value, 4).into_iter().enumerate() {
+                    cubies.edge_permutation[8 + slot] = 8 + edge as u8;
+                }
+            }
+        }
+        cubies
+    }
+}
+
+// The biggest coordinate a move table will be built for, so a table fits comfortably in memory
+const MAX_TABLE_COORDINATE: usize = 1 << 20;
+
+// Where every value of a coordinate goes under each of a list of moves
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct CoordinateTable {
+    coordinate: Coordinate,
+    moves: Vec<Move>,
+    table: Vec<u32>, // Indexed by value * moves.len() + the move's index in moves
+}
+
+impl CoordinateTable {
+    // Works out the table for every face move the coordinate can follow
+    pub(crate) fn new(coordinate: Coordinate) -> Result<Self, CubeError> {
+        Self::with_moves(coordinate, coordinate.moves())
+    }
+
+    // Works out the table by building a state for every value and moving it
+    // Edge permutation has far too many values for this, searches split it up instead
+    pub(crate) fn with_moves(coordinate: Coordinate, moves: Vec<Move>) -> Result<Self, CubeError> {
+        if coordinate.size() > MAX_TABLE_COORDINATE {
+            return Err(CubeError::TooLarge(format!(
+                "a move table with {} entries",
+                coordinate.size()
+            )));
+        }
+        // Anything else would mix up edges the coordinate keeps apart, and couldn't be undone
+        let allowed = coordinate.moves();
+        if let Some(m) = moves.iter().find(|m| !allowed.contains(m)) {
+            return Err(CubeError::InvalidState(format!(
+                "{coordinate:?} can't follow {m}, which moves edges in or out of the middle layer"
+            )));
+        }
+        let move_cubies = moves
+            .iter()
+            .map(CubieCube::of_move)
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut table = Vec::with_capacity(coordinate.size() * moves.len());
+        for value in 0..coordinate.size() {
+            let cubies = coordinate.cubies_at(value);
+            for m in &move_cubies {
+                table.push(coordinate.get(&cubies.then(m)) as u32);
+            }
+        }
+        Ok(Self {
+            coordinate,
+            moves,
+            table,
+        })
+    }
+
+    pub(crate) fn coordinate(&self) -> Coordinate {
+        self.coordinate
+    }
+
+    // The moves the table covers, in the order apply takes them
+    pub(crate) fn moves(&self) -> &[Move] {
+        &self.moves
+    }
+
+    // The value of the coordinate after doing the move at move_index
+    pub(crate) fn apply(&self, value: usize, move_index: usize) -> usize {
+        self.table[value * self.moves.len() + move_index] as usize
+    }
+}
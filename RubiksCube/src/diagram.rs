@@ -0,0 +1,111 @@
+use crate::notation::Algorithm;
+use crate::svg::{SvgOptions, SvgView};
+use crate::{CubeError, RubiksColor, RubiksCube, StickerId};
+
+// Pictures of the cases on algorithm sheets
+// A case is given by the algorithm that solves it, so the picture is of a solved cube with the
+// algorithm undone, and any sticker that doesn't matter at that stage is greyed out
+
+// Which stickers matter for a stage of solving, the rest are greyed out
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
+pub(crate) enum Stage {
+    Full, // Every sticker
+    F2l,  // Everything but the last layer
+    Ll,   // Only the last layer, for PLL and whole last layer cases
+    Oll,  // Only the last layer stickers with the up color, since OLL just needs them facing up
+}
+
+impl Stage {
+    fn shows(&self, cube: &RubiksCube, id: StickerId) -> bool {
+        // The last layer is the up face and the top row of every side
+        let last_layer = id.face == RubiksColor::UP || (id.y == 0 && id.face != RubiksColor::DOWN);
+        match self {
+            Stage::Full => true,
+            Stage::F2l => !last_layer,
+            Stage::Ll => last_layer,
+            Stage::Oll => {
+                last_layer && cube.try_get_square(id.face, id.x, id.y) == Ok(RubiksColor::UP)
+            }
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub(crate) struct Diagram {
+    pub(crate) face_size: usize,
+    pub(crate) stage: Stage,
+    pub(crate) arrows: bool, // Show where the algorithm moves each last layer sticker, for PLL
+    pub(crate) svg: SvgOptions, // How the picture is drawn, any mask here is kept as well
+}
+
+impl Default for Diagram {
+    fn default() -> Self {
+        Diagram {
+            face_size: 3,
+            stage: Stage::Full,
+            arrows: false,
+            svg: SvgOptions::default(),
+        }
+    }
+}
+
+impl Diagram {
+    // The last layer seen from above, with the top row of each side around the up face
+    pub(crate) fn last_layer(&self, case: &Algorithm) -> Result<String, CubeError> {
+        self.draw(case, SvgView::LastLayer)
+    }
+
+    // The cube from above and in front, showing the front right F2L slot
+    pub(crate) fn f2l(&self, case: &Algorithm) -> Result<String, CubeError> {
+        self.draw(case, SvgView::Isometric)
+    }
+
+    fn draw(&self, case: &Algorithm, view: SvgView) -> Result<String, CubeError> {
+        let cube = RubiksCube::try_new(self.face_size)?.try_do_algorithm(&case.inverse())?;
+        let mut options = SvgOptions {
+            view,
+            ..self.svg.clone()
+        };
+        let n = self.face_size;
+        for face in RubiksColor::ALL {
+            for y in 0..n {
+                for x in 0..n {
+                    let id = StickerId { face, x, y };
+                    if !self.stage.shows(&cube, id) {
+                        options.mask.push(id);
+                    }
+                }
+            }
+        }
+        if self.arrows {
+            options.arrows.extend(self.last_layer_arrows(case)?);
+        }
+        Ok(cube.to_svg(&options))
+    }
+
+    // Follows every up face sticker through the algorithm, pointing from where it starts in the
+    // case to where the algorithm puts it
+    // Only stickers that stay on the up face get an arrow, which for PLL is all of them
+    pub(crate) fn last_layer_arrows(
+        &self,
+        case: &Algorithm,
+    ) -> Result<Vec<(StickerId, StickerId)>, CubeError> {
+        let moved = RubiksCube::<StickerId>::solved(self.face_size).try_do_algorithm(case)?;
+        let n = self.face_size;
+        let mut arrows = vec![];
+        for y in 0..n {
+            for x in 0..n {
+                let to = StickerId {
+                    face: RubiksColor::UP,
+                    x,
+                    y,
+                };
+                let from = moved.try_get_square(RubiksColor::UP, x, y)?;
+                if from.face == RubiksColor::UP && from != to {
+                    arrows.push((from, to));
+                }
+            }
+        }
+        Ok(arrows)
+    }
+}
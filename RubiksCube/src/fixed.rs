@@ -0,0 +1,122 @@
+use std::array;
+
+use crate::{CubeError, CubeView, RubiksColor, RubiksCube, Sticker, TurnDirection};
+
+// A cube whose size is fixed at compile time
+// The stickers live in arrays instead of a Vec, so the cube can be copied around and turned
+// without ever touching the heap. Faces are stored in the same order and layout as RubiksCube
+
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
+pub(crate) struct FixedCube<const N: usize, S = RubiksColor> {
+    faces: [[[S; N]; N]; 6], // Indexed by face, then row, then column
+}
+
+pub(crate) type Cube2 = FixedCube<2>;
+pub(crate) type Cube3 = FixedCube<3>;
+
+impl<const N: usize> FixedCube<N> {
+    // Initializes a new solved cube
+    pub(crate) fn new() -> Self {
+        Self::solved()
+    }
+}
+
+impl<const N: usize> Default for FixedCube<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize, S: Sticker> FixedCube<N, S> {
+    // Initializes a new cube with every sticker in its solved position
+    pub(crate) fn solved() -> Self {
+        const { assert!(N > 0, "A cube needs at least one sticker on each face") };
+        Self {
+            faces: array::from_fn(|face| {
+                array::from_fn(|y| array::from_fn(|x| S::solved(RubiksColor::ALL[face], x, y)))
+            }),
+        }
+    }
+
+    // Every sticker in one slice, in the same order RubiksCube keeps them
+    fn stickers(&self) -> &[S] {
+        self.faces.as_flattened().as_flattened()
+    }
+
+    pub(crate) fn stickers_mut(&mut self) -> &mut [S] {
+        self.faces.as_flattened_mut().as_flattened_mut()
+    }
+
+    // Preform a move on a copy of this cube, see RubiksCube::do_move
+    // Panics if the layer doesn't exist
+    pub(crate) fn do_move(
+        &self,
+        face: RubiksColor,
+        layer: usize,
+        direction: TurnDirection,
+    ) -> Self {
+        let mut new = *self;
+        new.apply_move(face, layer, direction);
+        new
+    }
+
+    // Preform a move on this cube in place
+    // Panics if the layer doesn't exist, use try_apply_move when the layer comes from outside
+    pub(crate) fn apply_move(&mut self, face: RubiksColor, layer: usize, direction: TurnDirection) {
+        self.try_apply_move(face, layer, direction)
+            .expect("Tried to turn a layer the cube doesn't have")
+    }
+
+    pub(crate) fn try_apply_move(
+        &mut self,
+        face: RubiksColor,
+        layer: usize,
+        direction: TurnDirection,
+    ) -> Result<(), CubeError> {
+        if layer >= N {
+            return Err(CubeError::InvalidLayer {
+                layer,
+                face_size: N,
+            });
+        }
+        // Nothing to hash, so the move is only the sticker shuffling
+        CubeView::new(N, self.stickers_mut(), None).turn(face, layer, direction);
+        Ok(())
+    }
+
+    // Checks if every sticker is back where it started
+    pub(crate) fn is_solved(&self) -> bool
+    where
+        S: PartialEq,
+    {
+        *self == Self::solved()
+    }
+
+    // Gets the sticker at a position on a face, if the position is on the cube
+    pub(crate) fn get_square(&self, face: RubiksColor, x: usize, y: usize) -> Option<S> {
+        self.faces[face.index()].get(y)?.get(x).copied()
+    }
+}
+
+impl<const N: usize, S: Sticker> From<FixedCube<N, S>> for RubiksCube<S> {
+    fn from(cube: FixedCube<N, S>) -> Self {
+        RubiksCube::from_stickers(N, cube.stickers().to_vec())
+    }
+}
+
+impl<const N: usize, S: Sticker> TryFrom<&RubiksCube<S>> for FixedCube<N, S> {
+    type Error = CubeError;
+
+    // Fails if the cube isn't N stickers across
+    fn try_from(cube: &RubiksCube<S>) -> Result<Self, Self::Error> {
+        if cube.face_size != N {
+            return Err(CubeError::WrongSize {
+                face_size: cube.face_size,
+                expected: N,
+            });
+        }
+        let mut fixed = Self::solved();
+        fixed.stickers_mut().copy_from_slice(&cube.stickers);
+        Ok(fixed)
+    }
+}
@@ -0,0 +1,145 @@
+use crate::{CubeError, RubiksColor, StickerId};
+
+// Where every sticker is in space, for drawing the cube or working with it in 3D
+// The cube is centered on the origin and reaches from -1 to 1 along every axis. The axes are right
+// handed, with x pointing right, y up and z out of the front face towards whoever is looking at
+// it, so the up face is at y = 1 and the front face at z = 1
+// Looking at any face from outside, a sticker's x counts across to the right and its y counts down
+// from the top, the same way faces are stored. For the up face the top is the edge next to the
+// back face, and for the down face it is the edge next to the front face
+
+// How far a point can be from the middle of a sticker and still be taken for it, in cube units
+const POINT_TOLERANCE: f64 = 1e-6;
+
+// Where a sticker is and which way it faces, in cube units
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct StickerGeometry {
+    pub(crate) center: [f64; 3],
+    pub(crate) normal: [f64; 3], // Straight out of the face, one unit long
+}
+
+impl StickerId {
+    // Where this sticker is on a cube with faces face_size stickers across
+    pub(crate) fn geometry(&self, face_size: usize) -> Result<StickerGeometry, CubeError> {
+        if self.x >= face_size || self.y >= face_size {
+            return Err(CubeError::InvalidCoordinate {
+                x: self.x,
+                y: self.y,
+                face_size,
+            });
+        }
+        let n = face_size as f64;
+        Ok(StickerGeometry {
+            center: sticker_point(self.face, self.x, self.y, face_size).map(|c| c as f64 / n),
+            normal: face_normal(self.face),
+        })
+    }
+
+    // The sticker whose middle is at a point, going back the other way from geometry
+    pub(crate) fn at_point(point: [f64; 3], face_size: usize) -> Result<StickerId, CubeError> {
+        let error = || CubeError::InvalidPoint(format!("{point:?}"));
+        // Step 1: Back into the doubled units, where the middle of a sticker is all whole numbers
+        let scale = face_size as f64;
+        let doubled = point.map(|c| c * scale);
+        if face_size == 0
+            || doubled
+                .iter()
+                .any(|c| (c - c.round()).abs() > POINT_TOLERANCE * scale)
+        {
+            return Err(error());
+        }
+        let whole = doubled.map(|c| c.round() as i64);
+        // Step 2: The point has to be on exactly one face, and in the middle of a sticker across
+        // the other two axes
+        let n = face_size as i64;
+        let on_face = whole.iter().filter(|c| c.abs() == n).count();
+        let centered = whole
+            .iter()
+            .filter(|c| c.abs() < n && (*c + n - 1) % 2 == 0)
+            .count();
+        if on_face != 1 || centered != 2 {
+            return Err(error());
+        }
+        let (face, x, y) = point_sticker(whole, face_size);
+        Ok(StickerId { face, x, y })
+    }
+}
+
+// The direction straight out of a face
+pub(crate) fn face_normal(face: RubiksColor) -> [f64; 3] {
+    face_point(face, 0, 0, 1).map(|c| c as f64)
+}
+
+// Whether a point, in doubled units, is in a layer counted in from a face
+// Each layer is a slab two units deep, so the turning face's own stickers are in layer 0 and the
+// opposite face's are in the last layer
+pub(crate) fn in_layer(point: [i64; 3], face: RubiksColor, layer: usize, face_size: usize) -> bool {
+    let axis = face_point(face, 0, 0, 1);
+    let depth = axis[0] * point[0] + axis[1] * point[1] + axis[2] * point[2];
+    let outside = face_size as i64 - 2 * layer as i64;
+    (outside - 2..=outside).contains(&depth)
+}
+
+// Turns a point around a face's axis the way a layer of that face turns, quarter_turns clockwise
+// as the face is looked at, or counterclockwise if negative
+// Clockwise seen from outside the face is the negative way around its outward axis
+pub(crate) fn turn_point(point: [f64; 3], face: RubiksColor, quarter_turns: f64) -> [f64; 3] {
+    let axis = face_normal(face);
+    let angle = -quarter_turns * std::f64::consts::FRAC_PI_2;
+    let (sin, cos) = angle.sin_cos();
+    let across = [
+        axis[1] * point[2] - axis[2] * point[1],
+        axis[2] * point[0] - axis[0] * point[2],
+        axis[0] * point[1] - axis[1] * point[0],
+    ];
+    let along = (axis[0] * point[0] + axis[1] * point[1] + axis[2] * point[2]) * (1.0 - cos);
+    [0, 1, 2].map(|i| point[i] * cos + across[i] * sin + axis[i] * along)
+}
+
+// Every sticker sits at a point in space, with the cube centered on the origin and all
+// coordinates doubled so they stay whole numbers. Face positions run from -(n - 1) to n - 1 in
+// steps of 2, and the face itself is at n along its axis
+pub(crate) fn sticker_point(face: RubiksColor, x: usize, y: usize, face_size: usize) -> [i64; 3] {
+    let n = face_size as i64;
+    face_point(
+        face,
+        2 * x as i64 - (n - 1),
+        (n - 1) - 2 * y as i64,
+        face_size,
+    )
+}
+
+// The point on a face a across and b up from its middle, as the face is looked at from outside
+// Measured in the same doubled units, so the face's corners are at a and b of n or -n
+pub(crate) fn face_point(face: RubiksColor, a: i64, b: i64, face_size: usize) -> [i64; 3] {
+    let n = face_size as i64;
+    match face {
+        RubiksColor::FORWARD => [a, b, n],
+        RubiksColor::BACK => [-a, b, -n],
+        RubiksColor::UP => [a, n, -b],
+        RubiksColor::DOWN => [a, -n, b],
+        RubiksColor::RIGHT => [n, b, -a],
+        RubiksColor::LEFT => [-n, b, a],
+    }
+}
+
+// Goes back from a point to the sticker there
+// Only the face a sticker is on reaches n along an axis, so that picks out the face
+pub(crate) fn point_sticker(point: [i64; 3], face_size: usize) -> (RubiksColor, usize, usize) {
+    let n = face_size as i64;
+    let [px, py, pz] = point;
+    let (face, a, b) = if pz == n {
+        (RubiksColor::FORWARD, px, py)
+    } else if pz == -n {
+        (RubiksColor::BACK, -px, py)
+    } else if py == n {
+        (RubiksColor::UP, px, -pz)
+    } else if py == -n {
+        (RubiksColor::DOWN, px, pz)
+    } else if px == n {
+        (RubiksColor::RIGHT, -pz, py)
+    } else {
+        (RubiksColor::LEFT, pz, py)
+    };
+    (face, ((a + n - 1) / 2) as usize, ((n - 1 - b) / 2) as usize)
+}
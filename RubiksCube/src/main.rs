@@ -1,63 +1,210 @@
-use std::collections::HashMap;
+// Some of the cube's operations are only built on by the tests so far, so unused code is only
+// reported in the test build, where it really is unused
+#![cfg_attr(not(test), allow(dead_code))]
+// Faces and directions are spelled in capitals throughout
+#![allow(clippy::upper_case_acronyms)]
+
+use std::collections::HashSet;
+use std::fmt;
+
+mod animation;
+mod bench;
+mod coordinates;
+mod diagram;
+mod fixed;
+mod geometry;
+mod mesh;
+mod move_table;
+mod net;
+mod notation;
+mod packed;
+mod parallel;
+mod pattern_db;
+mod render;
+mod repl;
+mod scramble;
+mod solver;
+mod svg;
+mod symmetry;
+mod tui;
 
 // Code for representing a Rubiks cube of arbitrary size
 
-fn write_2d_vec<T>(v: &mut Vec<T>, size: &u8, x: &u8, y: &u8, val: T) {
-    v[usize::from(x + y * size)] = val;
+// Everything that can go wrong when asking the cube to do something it can't
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum CubeError {
+    InvalidLayer {
+        layer: usize,
+        face_size: usize,
+    },
+    InvalidSize(usize),
+    WrongSize {
+        face_size: usize,
+        expected: usize, // The only size whatever was asked can work with
+    },
+    InvalidCoordinate {
+        x: usize,
+        y: usize,
+        face_size: usize,
+    },
+    Parse(String),
+    InvalidState(String),
+    Io(String), // Kept as the message so errors can still be compared and cloned
+    TooLarge(String),
+    NotFound(String),     // A search gave up, with why
+    InvalidPoint(String), // A point in space that isn't the middle of any sticker
+}
+
+impl fmt::Display for CubeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CubeError::InvalidLayer { layer, face_size } => write!(
+                f,
+                "layer {layer} does not exist on a cube with {face_size} layers"
+            ),
+            CubeError::InvalidSize(size) => write!(f, "a cube can't have a face size of {size}"),
+            CubeError::WrongSize {
+                face_size,
+                expected,
+            } => write!(
+                f,
+                "expected a {expected}x{expected} cube, not a {face_size}x{face_size}"
+            ),
+            CubeError::InvalidCoordinate { x, y, face_size } => write!(
+                f,
+                "({x}, {y}) is off the edge of a {face_size}x{face_size} face"
+            ),
+            CubeError::Parse(message) => write!(f, "couldn't parse {message}"),
+            CubeError::InvalidState(message) => write!(f, "impossible cube: {message}"),
+            CubeError::Io(message) => write!(f, "file error: {message}"),
+            CubeError::TooLarge(message) => write!(f, "{message} would be too large"),
+            CubeError::NotFound(message) => write!(f, "no solution found {message}"),
+            CubeError::InvalidPoint(point) => write!(f, "there's no sticker at {point}"),
+        }
+    }
+}
+
+impl std::error::Error for CubeError {}
+
+impl From<std::io::Error> for CubeError {
+    fn from(error: std::io::Error) -> Self {
+        CubeError::Io(error.to_string())
+    }
 }
 
-fn read_2d_vec<T>(v: &Vec<T>, size: &u8, x: &u8, y: &u8) -> Option<&T> {
-    v.get(usize::from(x + y * size))
+fn write_2d_vec<T>(v: &mut [T], size: &usize, x: &usize, y: &usize, val: T) {
+    v[x + y * size] = val;
 }
 
-// Function to rotate a 2d array clockwise
-// Transposes the matrix and reverses the rows
-// | A B |
-// | C D |
-// Transposed
-// | A C |
-// | B D |
-// Reversed
-// | C A |
-// | D B |
-fn rotate_vec_cw(base: &Vec<RubiksColor>, size: &u8) -> Vec<RubiksColor> {
-    // Step 1: Initialize a new vec that will hold the array
-    let mut r_vec = vec![RubiksColor::UP; usize::from(size * size)];
-    // Step 2: Itterate over x y positions of input, write them to output
-    for x in 0..*size {
-        for y in 0..*size {
-            let base_value = read_2d_vec(&base, *size, &x, &y)
-                .expect("This shouldn't happen unless you are lying to the API, naughty.");
-            let mut tx = y;
-            let ty = x;
-            tx = (size - 1) - tx;
-            write_2d_vec(&mut r_vec, *size, &tx, &ty, *base_value);
+fn read_2d_vec<'a, T>(v: &'a [T], size: &usize, x: &usize, y: &usize) -> Option<&'a T> {
+    v.get(x + y * size)
+}
+
+// Functions to rotate a 2d array in place
+// Every square is part of a ring of four that trade places with each other, so walking a quarter
+// of the grid and cycling each ring moves every square exactly once without a second buffer
+// | A B |    | C A |
+// | C D | -> | D B |
+// On odd sized grids the middle square stays where it is, but still gets spun around
+// When the grid is a face of a hashed cube, the tracker keeps the hash in step with every move
+fn rotate_vec_cw<S: Sticker>(base: &mut [S], size: &usize, tracker: Option<HashTracker>) {
+    rotate_vec(base, size, &TurnDirection::CLOCKWISE, tracker);
+}
+
+fn rotate_vec_ccw<S: Sticker>(base: &mut [S], size: &usize, tracker: Option<HashTracker>) {
+    rotate_vec(base, size, &TurnDirection::COUNTERCLOCKWISE, tracker);
+}
+
+fn rotate_vec<S: Sticker>(
+    base: &mut [S],
+    size: &usize,
+    direction: &TurnDirection,
+    mut tracker: Option<HashTracker>,
+) {
+    let last = size - 1;
+    for y in 0..size / 2 {
+        for x in 0..size.div_ceil(2) {
+            // Clockwise, (x, y) moves to (last - y, x), which moves to (last - x, last - y) and so on
+            let ring = [
+                x + y * size,
+                (last - y) + x * size,
+                (last - x) + (last - y) * size,
+                y + (last - x) * size,
+            ];
+            cycle_4(base, ring, direction, tracker.as_mut());
+        }
+    }
+    if size % 2 == 1 {
+        let middle = size / 2 + (size / 2) * size;
+        let before = base[middle];
+        base[middle] = before.turned(direction.quarter_turns());
+        if let Some(tracker) = tracker.as_mut() {
+            tracker.replace(middle, before, base[middle]);
         }
     }
-    return r_vec;
 }
 
-fn rotate_vec_ccw(base: &Vec<RubiksColor>, size: &u8) -> Vec<RubiksColor> {
-    // Step 1: Initialize a new vec that will hold the array
-    let mut r_vec = vec![RubiksColor::UP; usize::from(size * size)];
-    // Step 2: Itterate over x y positions of input, write them to output
-    for x in 0..*size {
-        for y in 0..*size {
-            let base_value = read_2d_vec(&base, *size, x, y)
-                .expect("This shouldn't happen unless you are lying to the API, naughty.");
-            let mut tx = (size - 1) - x;
-            let mut ty = y;
-            let temp = tx;
-            tx = ty;
-            ty = temp;
-            write_2d_vec(&mut r_vec, *size, tx, ty, *base_value);
+// Moves the squares at four indices one step along the ring, spinning each one as it goes
+fn cycle_4<S: Sticker>(
+    base: &mut [S],
+    ring: [usize; 4],
+    direction: &TurnDirection,
+    tracker: Option<&mut HashTracker>,
+) {
+    let twist = direction.quarter_turns();
+    let before = ring.map(|i| base[i]);
+    match direction {
+        TurnDirection::CLOCKWISE => {
+            let last = base[ring[3]];
+            base[ring[3]] = base[ring[2]].turned(twist);
+            base[ring[2]] = base[ring[1]].turned(twist);
+            base[ring[1]] = base[ring[0]].turned(twist);
+            base[ring[0]] = last.turned(twist);
+        }
+        TurnDirection::COUNTERCLOCKWISE => {
+            let first = base[ring[0]];
+            base[ring[0]] = base[ring[1]].turned(twist);
+            base[ring[1]] = base[ring[2]].turned(twist);
+            base[ring[2]] = base[ring[3]].turned(twist);
+            base[ring[3]] = first.turned(twist);
         }
     }
-    return r_vec;
+    if let Some(tracker) = tracker {
+        for k in 0..4 {
+            tracker.replace(ring[k], before[k], base[ring[k]]);
+        }
+    }
+}
+
+// Zobrist style hashing, where a cube's hash is every sticker's key xored together
+// Moving a sticker only needs its old key xored out and its new key xored in, so a move updates
+// the hash by touching just the stickers it moves
+// Keys are mixed up from the sticker's index and code rather than drawn from a random table, so
+// every size of cube and every kind of sticker can be hashed without storing anything
+fn sticker_key<S: Sticker>(index: usize, sticker: S) -> u64 {
+    let mut key = (index as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15) ^ sticker.code();
+    key = (key ^ (key >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    key = (key ^ (key >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    key ^ (key >> 31)
+}
+
+// Keeps a cube's hash up to date while the stickers of one part of it are moved around
+struct HashTracker<'a> {
+    hash: &'a mut u64,
+    first_index: usize, // Where the part being worked on starts on the whole cube
+}
+
+impl HashTracker<'_> {
+    fn replace<S: Sticker>(&mut self, index: usize, before: S, after: S) {
+        let index = self.first_index + index;
+        *self.hash ^= sticker_key(index, before) ^ sticker_key(index, after);
+    }
 }
 
 // Face Colors are represented by their direction in the "solved" state
-#[derive(Clone, Copy, Hash, PartialEq, Eq)]
+// Each one fits in a single byte, so a cube's stickers take up one byte each
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
+#[repr(u8)]
 enum RubiksColor {
     UP,
     DOWN,
@@ -68,24 +215,116 @@ enum RubiksColor {
 }
 
 impl RubiksColor {
-    fn opposite(&self) -> RubiksColor {
-        match self {
-            RubiksColor::UP => RubiksColor::DOWN,
-            RubiksColor::DOWN => RubiksColor::UP,
-            RubiksColor::LEFT => RubiksColor::RIGHT,
-            RubiksColor::RIGHT => RubiksColor::LEFT,
-            RubiksColor::FORWARD => RubiksColor::BACK,
-            RubiksColor::BACK => RubiksColor::FORWARD,
+    // Every face, in the order they are laid out when walking the cube
+    const ALL: [RubiksColor; 6] = [
+        RubiksColor::UP,
+        RubiksColor::DOWN,
+        RubiksColor::LEFT,
+        RubiksColor::RIGHT,
+        RubiksColor::FORWARD,
+        RubiksColor::BACK,
+    ];
+
+    // Where this face sits in ALL, which is also the order faces are stored on the cube
+    fn index(&self) -> usize {
+        *self as usize
+    }
+}
+
+// Anything that can be placed on the faces of a cube
+// The moves only ever shuffle stickers around, so all a sticker needs to know is what it looks like
+// when the cube is solved
+trait Sticker: Copy {
+    fn solved(face: RubiksColor, x: usize, y: usize) -> Self;
+
+    // A number that tells this sticker apart from any other sticker of the same kind, for hashing
+    fn code(&self) -> u64;
+
+    // Called whenever a sticker is spun in place relative to the grid of the face it lands on
+    // Quarter turns are clockwise, plain colors look the same whichever way up they are
+    fn turned(self, _quarter_turns: u8) -> Self {
+        self
+    }
+}
+
+impl Sticker for RubiksColor {
+    fn solved(face: RubiksColor, _x: usize, _y: usize) -> Self {
+        face
+    }
+
+    fn code(&self) -> u64 {
+        self.index() as u64
+    }
+}
+
+// A sticker that remembers where it started on a solved cube
+// Colors alone can't tell two stickers of the same face apart, so a cube of these is what lets us
+// treat a cube state as a permutation of the stickers
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
+struct StickerId {
+    face: RubiksColor,
+    x: usize,
+    y: usize,
+}
+
+impl Sticker for StickerId {
+    fn solved(face: RubiksColor, x: usize, y: usize) -> Self {
+        Self { face, x, y }
+    }
+
+    // Coordinates are packed in 29 bits each, far more than any cube that fits in memory needs
+    fn code(&self) -> u64 {
+        self.face.code() | (self.x as u64) << 3 | (self.y as u64) << 32
+    }
+}
+
+// A sticker with a picture on it, as found on picture cubes and supercubes
+// Spinning one of these in place is visible, so along with where it started it keeps track of
+// how far it has been turned
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
+struct SuperSticker {
+    id: StickerId,
+    orientation: u8, // Quarter turns clockwise from upright, relative to the face it is on
+}
+
+impl Sticker for SuperSticker {
+    fn solved(face: RubiksColor, x: usize, y: usize) -> Self {
+        Self {
+            id: StickerId::solved(face, x, y),
+            orientation: 0,
+        }
+    }
+
+    fn code(&self) -> u64 {
+        self.id.code() | u64::from(self.orientation) << 61
+    }
+
+    fn turned(self, quarter_turns: u8) -> Self {
+        Self {
+            id: self.id,
+            orientation: (self.orientation + quarter_turns) % 4,
         }
     }
 }
 
+// A cube where centers can be seen to be twisted
+type SuperCube = RubiksCube<SuperSticker>;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 enum TurnDirection {
     CLOCKWISE,
     COUNTERCLOCKWISE,
 }
 
 impl TurnDirection {
+    // How many clockwise quarter turns this direction is
+    fn quarter_turns(&self) -> u8 {
+        match self {
+            TurnDirection::CLOCKWISE => 1,
+            TurnDirection::COUNTERCLOCKWISE => 3,
+        }
+    }
+
     fn opposite(&self) -> TurnDirection {
         match self {
             TurnDirection::CLOCKWISE => TurnDirection::COUNTERCLOCKWISE,
@@ -94,506 +333,2140 @@ impl TurnDirection {
     }
 }
 
-#[derive(Clone)]
-struct RubiksCube {
-    face_size: u8,                                 // The edge length of the cube
-    faces: HashMap<RubiksColor, Vec<RubiksColor>>, // TODO: Some data structure to represent the faces
+// One side of a turning layer: the face it's on, where its i'th sticker sits on that face, and
+// how far a sticker is spun relative to that face's grid when it arrives going clockwise
+type Strip<'a> = (RubiksColor, &'a dyn Fn(usize) -> (usize, usize), u8);
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct RubiksCube<S = RubiksColor> {
+    face_size: usize, // The edge length of the cube
+    hash: u64,        // The Zobrist hash of the stickers, kept up to date by every move
+    stickers: Vec<S>, // Every face's row major face_size * face_size grid, one after another
+                      // in the same order as RubiksColor::ALL
+}
+
+// The most stickers try_new will put on a cube, which is a 1000x1000
+// Anything bigger takes longer to draw than anyone would wait for
+const MAX_STICKERS: usize = 6 * 1000 * 1000;
+
+// Equal cubes always have equal Zobrist hashes, so that's all that needs hashing
+impl<S: Sticker> std::hash::Hash for RubiksCube<S> {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.face_size.hash(state);
+        self.zobrist_hash().hash(state);
+    }
 }
 
 impl RubiksCube {
     // Initializes a new cube of given size with all faces set properly
-    fn new(face_size: u8) -> Self {
-        let face_squares: usize = (face_size * face_size).into();
-
-        let u_face = vec![RubiksColor::UP; face_squares];
-        let d_face = vec![RubiksColor::DOWN; face_squares];
-        let l_face = vec![RubiksColor::LEFT; face_squares];
-        let r_face = vec![RubiksColor::RIGHT; face_squares];
-        let f_face = vec![RubiksColor::FORWARD; face_squares];
-        let b_face = vec![RubiksColor::BACK; face_squares];
-        let faces = HashMap::from([
-            (RubiksColor::UP, u_face),
-            (RubiksColor::DOWN, d_face),
-            (RubiksColor::LEFT, l_face),
-            (RubiksColor::RIGHT, r_face),
-            (RubiksColor::FORWARD, f_face),
-            (RubiksColor::BACK, b_face),
-        ]);
-        Self { face_size, faces }
+    fn new(face_size: usize) -> Self {
+        Self::solved(face_size)
+    }
+
+    // Same as new, but refuses to build a cube with no stickers on it, or with more than
+    // MAX_STICKERS so a mistyped size can't ask for more memory than there is
+    fn try_new(face_size: usize) -> Result<Self, CubeError> {
+        if face_size == 0 {
+            return Err(CubeError::InvalidSize(face_size));
+        }
+        let stickers = face_size
+            .checked_mul(face_size)
+            .and_then(|squares| squares.checked_mul(6));
+        if stickers.is_none_or(|stickers| stickers > MAX_STICKERS) {
+            return Err(CubeError::TooLarge(format!(
+                "a {face_size}x{face_size} cube"
+            )));
+        }
+        Ok(Self::new(face_size))
+    }
+}
+
+impl<S: Sticker> RubiksCube<S> {
+    // Initializes a new cube of given size with every sticker in its solved position
+    fn solved(face_size: usize) -> Self {
+        let mut stickers = Vec::with_capacity(6 * face_size * face_size);
+        for face in RubiksColor::ALL {
+            for y in 0..face_size {
+                for x in 0..face_size {
+                    stickers.push(S::solved(face, x, y));
+                }
+            }
+        }
+        Self::from_stickers(face_size, stickers)
+    }
+
+    // Builds a cube from all of its stickers, laid out the way the cube stores them
+    fn from_stickers(face_size: usize, stickers: Vec<S>) -> Self {
+        Self {
+            face_size,
+            hash: Self::full_hash(&stickers),
+            stickers,
+        }
     }
 
+    // Works out the Zobrist hash of a set of stickers from scratch
+    fn full_hash(stickers: &[S]) -> u64 {
+        stickers
+            .iter()
+            .enumerate()
+            .fold(0, |hash, (index, sticker)| {
+                hash ^ sticker_key(index, *sticker)
+            })
+    }
+
+    // Works the hash out again after stickers have been changed by hand
+    fn rehash(&mut self) {
+        self.hash = Self::full_hash(&self.stickers);
+    }
+
+    // The hash of this cube's state, which stays up to date as the cube is turned
+    fn zobrist_hash(&self) -> u64 {
+        self.hash
+    }
+}
+
+impl<S: Sticker> RubiksCube<S> {
     // Preform a move on a rubiks cube
     // Takes in the targeted face, how many layers down from that face is the layer being turned,
     // and the turning drection
     // NOTE: Layers are 0 indexed, passing in 0 will turn the whole input face
+    // Returns the turned cube and leaves this one alone, use apply_move to turn it in place
+    // Panics if the layer doesn't exist, use try_do_move when the layer comes from outside
     fn do_move(
         &mut self,
         face_target: RubiksColor,
-        layer: u8,
+        layer: usize,
         direction: TurnDirection,
-    ) -> RubiksCube {
-        let new = self.clone();
-        match face_target {
-            RubiksColor::UP => new.rotate_top(&direction, &layer),
-            RubiksColor::DOWN => {
-                new.rotate_top(&direction.opposite(), &(self.face_size.clone() - layer - 1))
-            }
+    ) -> RubiksCube<S> {
+        self.try_do_move(face_target, layer, direction)
+            .expect("Tried to turn a layer the cube doesn't have")
+    }
 
-            RubiksColor::RIGHT => new.rotate_right(&direction, &layer),
-            RubiksColor::LEFT => {
-                new.rotate_right(&direction.opposite(), &(self.face_size.clone() - layer - 1))
-            }
-            RubiksColor::FORWARD => new.rotate_front(&direction, &layer),
-            RubiksColor::BACK => {
-                new.rotate_front(&direction.opposite(), &(self.face_size.clone() - layer - 1))
-            }
+    // Same as do_move, but reports layers that are out of range instead of panicking
+    fn try_do_move(
+        &mut self,
+        face_target: RubiksColor,
+        layer: usize,
+        direction: TurnDirection,
+    ) -> Result<RubiksCube<S>, CubeError> {
+        let mut new = self.clone();
+        new.try_apply_move(face_target, layer, direction)?;
+        Ok(new)
+    }
+
+    // Preform a move on this cube in place
+    // Nothing is allocated, so this is the one to use when doing lots of moves
+    fn apply_move(&mut self, face_target: RubiksColor, layer: usize, direction: TurnDirection) {
+        self.try_apply_move(face_target, layer, direction)
+            .expect("Tried to turn a layer the cube doesn't have")
+    }
+
+    // Same as apply_move, but reports layers that are out of range instead of panicking
+    fn try_apply_move(
+        &mut self,
+        face_target: RubiksColor,
+        layer: usize,
+        direction: TurnDirection,
+    ) -> Result<(), CubeError> {
+        if layer >= self.face_size {
+            return Err(CubeError::InvalidLayer {
+                layer,
+                face_size: self.face_size,
+            });
+        }
+        CubeView::new(self.face_size, &mut self.stickers, Some(&mut self.hash)).turn(
+            face_target,
+            layer,
+            direction,
+        );
+        Ok(())
+    }
+
+    // Checks if every sticker is back where it started
+    // For supercubes this includes being the right way up, so a twisted center isn't solved
+    fn is_solved(&self) -> bool
+    where
+        S: PartialEq,
+    {
+        self.stickers == Self::solved(self.face_size).stickers
+    }
+}
+
+// A cube's stickers borrowed for turning, laid out the same way RubiksCube stores them
+// Cubes kept in other containers share the move code by handing over their stickers as one slice,
+// along with their hash if they keep one
+struct CubeView<'a, S> {
+    face_size: usize,
+    stickers: &'a mut [S],
+    hash: Option<&'a mut u64>,
+}
+
+impl<'a, S: Sticker> CubeView<'a, S> {
+    fn new(face_size: usize, stickers: &'a mut [S], hash: Option<&'a mut u64>) -> Self {
+        Self {
+            face_size,
+            stickers,
+            hash,
+        }
+    }
+
+    // Turns one layer, the layer has to already be checked against the size of the cube
+    // Inlined so cubes with a size known at compile time get move code built for that size
+    #[inline(always)]
+    fn turn(&mut self, face_target: RubiksColor, layer: usize, direction: TurnDirection) {
+        let opposite_layer = self.face_size - layer - 1;
+        match face_target {
+            RubiksColor::UP => self.rotate_top(&direction, &layer),
+            RubiksColor::DOWN => self.rotate_top(&direction.opposite(), &opposite_layer),
+            RubiksColor::RIGHT => self.rotate_right(&direction, &layer),
+            RubiksColor::LEFT => self.rotate_right(&direction.opposite(), &opposite_layer),
+            RubiksColor::FORWARD => self.rotate_front(&direction, &layer),
+            RubiksColor::BACK => self.rotate_front(&direction.opposite(), &opposite_layer),
         }
     }
 
     // Rotates the cube along the axis facing the front direction
-    fn rotate_front(mut self, direction: &TurnDirection, layer: &u8) -> RubiksCube {
+    #[inline(always)]
+    fn rotate_front(&mut self, direction: &TurnDirection, layer: &usize) {
+        let last = self.face_size - 1;
+        let layer = *layer;
         // Step 1: Determine if the front face is being rotated, if so rotate it
-        if *layer == 0 {
-            let face = self
-                .faces
-                .get_mut(&RubiksColor::FORWARD)
-                .unwrap() // We will never not have this be filled out so we can just unwrap here
-                .to_owned();
-            let new_face = match direction {
-                TurnDirection::CLOCKWISE => rotate_vec_cw(&face, &self.face_size),
-                TurnDirection::COUNTERCLOCKWISE => rotate_vec_ccw(&face, &self.face_size),
-            };
-            self.faces.insert(RubiksColor::FORWARD, new_face);
+        if layer == 0 {
+            self.rotate_face(RubiksColor::FORWARD, direction);
         }
         // Step 2: Determine if the back face is being rotated, if so rotate it
-        if *layer == self.face_size - 1 {
-            let face = self.faces.get_mut(&RubiksColor::BACK).unwrap().to_owned();
-            let new_face = match direction {
-                TurnDirection::CLOCKWISE => rotate_vec_ccw(&face, &self.face_size),
-                TurnDirection::COUNTERCLOCKWISE => rotate_vec_cw(&face, &self.face_size),
-            };
-            self.faces.insert(RubiksColor::BACK, new_face);
-        }
-        // Step 3: Determine the strips allong the top, bottom, left, and right that are going to
-        // be swapped
-        // Step 3.1: TOP [0, Size - 1 - Layer] -> [Size - 1, Size - 1 - Layer]
-        let mut top_strip: Vec<RubiksColor> = vec![];
-        let mut top_face = self.faces.get_mut(&RubiksColor::UP).unwrap().to_owned();
-        for x in 0..self.face_size {
-            top_strip.push(
-                read_2d_vec(
-                    &top_face,
-                    &self.face_size,
-                    &x,
-                    &(self.face_size - 1 - layer),
-                )
-                .unwrap()
-                .clone(),
-            );
+        if layer == last {
+            self.rotate_face(RubiksColor::BACK, &direction.opposite());
         }
-        // Step 3.2: RIGHT [Layer, 0] -> [Layer, Size -1]
-        let mut right_strip: Vec<RubiksColor> = vec![];
-        let mut right_face = self.faces.get_mut(&RubiksColor::RIGHT).unwrap().to_owned();
-        for y in 0..self.face_size {
-            right_strip.push(
-                read_2d_vec(&right_face, &self.face_size, &layer, &y)
-                    .unwrap()
-                    .clone(),
-            );
+        // Step 3: Cycle the strips going clockwise around the front, starting from the top one
+        // Everything in the layer spins with it, so each sticker is turned the same way the layer is
+        self.cycle_strips(
+            direction,
+            [
+                (RubiksColor::UP, &|i| (i, last - layer), 1),
+                (RubiksColor::RIGHT, &|i| (layer, i), 1),
+                (RubiksColor::DOWN, &|i| (last - i, layer), 1),
+                (RubiksColor::LEFT, &|i| (last - layer, last - i), 1),
+            ],
+        );
+    }
+
+    // Rotates the cube along the axis facing the top direction
+    #[inline(always)]
+    fn rotate_top(&mut self, direction: &TurnDirection, layer: &usize) {
+        let last = self.face_size - 1;
+        let layer = *layer;
+        // Step 1: Determine if the top face is being rotated, if so rotate it
+        if layer == 0 {
+            self.rotate_face(RubiksColor::UP, direction);
         }
-        // Step 3.3: BOTTOM [Size -1, Layer] -> [0, Layer]
-        let mut bottom_strip: Vec<RubiksColor> = vec![];
-        let mut bottom_face = self.faces.get_mut(&RubiksColor::DOWN).unwrap().to_owned();
-        for x in (0..self.face_size).rev() {
-            bottom_strip.push(
-                read_2d_vec(
-                    &bottom_face,
-                    &self.face_size,
-                    &x,
-                    &(self.face_size - 1 - layer),
-                )
-                .unwrap()
-                .clone(),
-            );
+        // Step 2: Determine if the bottom face is being rotated, if so rotate it
+        if layer == last {
+            self.rotate_face(RubiksColor::DOWN, &direction.opposite());
         }
-        // Step 3.4: LEFT [Size - Layer, Size] -> [Size - Layer, 0]
-        let mut left_strip: Vec<RubiksColor> = vec![];
-        let mut left_face = self.faces.get_mut(&RubiksColor::LEFT).unwrap().to_owned();
-        for y in (0..self.face_size).rev() {
-            left_strip.push(
-                read_2d_vec(
-                    &left_face,
-                    &self.face_size,
-                    &(self.face_size - 1 - layer),
-                    &y,
-                )
-                .unwrap()
-                .clone(),
-            );
+        // Step 3: Cycle the strips going clockwise around the top, starting from the front one
+        // The side faces all have the top on their first row, so every strip is read the same way
+        self.cycle_strips(
+            direction,
+            [
+                (RubiksColor::FORWARD, &|i| (i, layer), 0),
+                (RubiksColor::LEFT, &|i| (i, layer), 0),
+                (RubiksColor::BACK, &|i| (i, layer), 0),
+                (RubiksColor::RIGHT, &|i| (i, layer), 0),
+            ],
+        );
+    }
+
+    // Rotates the cube along the axis facing the right direction
+    #[inline(always)]
+    fn rotate_right(&mut self, direction: &TurnDirection, layer: &usize) {
+        let last = self.face_size - 1;
+        let layer = *layer;
+        // Step 1: Determine if the right face is being rotated, if so rotate it
+        if layer == 0 {
+            self.rotate_face(RubiksColor::RIGHT, direction);
+        }
+        // Step 2: Determine if the left face is being rotated, if so rotate it
+        if layer == last {
+            self.rotate_face(RubiksColor::LEFT, &direction.opposite());
         }
-        // Step 4: Swap the faces forwards if clockwise, backwards if counterclockwise
-        let mut new_top: Vec<RubiksColor>;
-        let mut new_right: Vec<RubiksColor>;
-        let mut new_bottom: Vec<RubiksColor>;
-        let mut new_left: Vec<RubiksColor>;
+        // Step 3: Cycle the strips going clockwise around the right, starting from the front one
+        // The back face is viewed from behind, so the right layers are on its left side and anything
+        // moving onto or off of it ends up upside down
+        self.cycle_strips(
+            direction,
+            [
+                (RubiksColor::FORWARD, &|i| (last - layer, i), 0),
+                (RubiksColor::UP, &|i| (last - layer, i), 0),
+                (RubiksColor::BACK, &|i| (layer, last - i), 2),
+                (RubiksColor::DOWN, &|i| (last - layer, i), 2),
+            ],
+        );
+    }
 
+    // Spins all the stickers on one face around its center
+    fn rotate_face(&mut self, face: RubiksColor, direction: &TurnDirection) {
+        let size = self.face_size;
+        let face_squares = size * size;
+        let stickers = &mut self.stickers[face.index() * face_squares..][..face_squares];
+        let tracker = self.hash.as_deref_mut().map(|hash| HashTracker {
+            hash,
+            first_index: face.index() * face_squares,
+        });
         match direction {
-            TurnDirection::CLOCKWISE => {
-                new_top = left_strip;
-                new_right = top_strip;
-                new_bottom = right_strip;
-                new_left = bottom_strip;
-            }
-            TurnDirection::COUNTERCLOCKWISE => {
-                new_top = right_strip;
-                new_right = bottom_strip;
-                new_bottom = left_strip;
-                new_left = top_strip;
+            TurnDirection::CLOCKWISE => rotate_vec_cw(stickers, &size, tracker),
+            TurnDirection::COUNTERCLOCKWISE => rotate_vec_ccw(stickers, &size, tracker),
+        }
+    }
+
+    // Moves the stickers of four strips one step around a layer
+    // Clockwise the i'th sticker of each strip moves to the i'th spot of the next strip, and
+    // counterclockwise everything goes back the other way
+    // Inlined so the strip positions are worked out in the loop instead of called through a pointer
+    #[inline(always)]
+    fn cycle_strips(&mut self, direction: &TurnDirection, strips: [Strip; 4]) {
+        let twists = strips.each_ref().map(|s| s.2);
+        let untwists = twists.map(|twist| (4 - twist) % 4);
+        for i in 0..self.face_size {
+            let spots = strips.each_ref().map(|(face, position, _)| {
+                let (x, y) = position(i);
+                face.index() * self.face_size * self.face_size + x + y * self.face_size
+            });
+            let stickers = &mut *self.stickers;
+            let before = spots.map(|spot| stickers[spot]);
+            match direction {
+                TurnDirection::CLOCKWISE => {
+                    let last = stickers[spots[3]];
+                    for k in (1..4).rev() {
+                        stickers[spots[k]] = stickers[spots[k - 1]].turned(twists[k]);
+                    }
+                    stickers[spots[0]] = last.turned(twists[0]);
+                }
+                TurnDirection::COUNTERCLOCKWISE => {
+                    let first = stickers[spots[0]];
+                    for k in 0..3 {
+                        stickers[spots[k]] = stickers[spots[k + 1]].turned(untwists[k + 1]);
+                    }
+                    stickers[spots[3]] = first.turned(untwists[0]);
+                }
+            }
+            if let Some(hash) = self.hash.as_deref_mut() {
+                let mut tracker = HashTracker {
+                    hash,
+                    first_index: 0,
+                };
+                for k in 0..4 {
+                    tracker.replace(spots[k], before[k], stickers[spots[k]]);
+                }
             }
-        };
-        // Step 5: Write new strips to new faces
-        new_top.reverse();
-        for x in 0..self.face_size {
-            write_2d_vec(
-                &mut top_face,
-                &self.face_size,
-                &x,
-                &(self.face_size - 1 - layer),
-                new_top.pop().unwrap(),
-            )
         }
-        new_right.reverse();
-        for y in 0..self.face_size {
-            write_2d_vec(
-                &mut right_face,
-                &self.face_size,
-                &layer,
-                &y,
-                new_right.pop().unwrap(),
-            )
+    }
+}
+
+impl<S: Sticker> RubiksCube<S> {
+    // Where the sticker at a position on a face is kept in the cube's buffer
+    fn sticker_index(&self, face: RubiksColor, x: usize, y: usize) -> usize {
+        face.index() * self.face_size * self.face_size + x + y * self.face_size
+    }
+
+    // The row major grid of stickers on one face
+    fn face(&self, face: RubiksColor) -> &[S] {
+        let face_squares = self.face_size * self.face_size;
+        &self.stickers[face.index() * face_squares..(face.index() + 1) * face_squares]
+    }
+
+    // Changing stickers through this doesn't update the hash, so call rehash once done
+    fn face_mut(&mut self, face: RubiksColor) -> &mut [S] {
+        let face_squares = self.face_size * self.face_size;
+        &mut self.stickers[face.index() * face_squares..(face.index() + 1) * face_squares]
+    }
+
+    // Builds a new cube by converting every sticker, keeping each one in its current position
+    fn map<T: Sticker>(&self, f: impl Fn(S) -> T) -> RubiksCube<T> {
+        RubiksCube::from_stickers(
+            self.face_size,
+            self.stickers.iter().map(|s| f(*s)).collect(),
+        )
+    }
+
+    // Applies the permutation of a labelled cube on top of this one
+    // The square at each position takes whatever this cube has at the position the labelled cube's
+    // sticker came from, which is the same as playing the labelled cube's moves after this cube's
+    // Panics if the cubes are different sizes, use try_compose when they might be
+    fn compose(&self, other: &RubiksCube<StickerId>) -> RubiksCube<S> {
+        self.try_compose(other)
+            .expect("Can only compose cubes of the same size")
+    }
+
+    // Same as compose, but reports cubes of different sizes instead of panicking
+    fn try_compose(&self, other: &RubiksCube<StickerId>) -> Result<RubiksCube<S>, CubeError> {
+        if self.face_size != other.face_size {
+            return Err(CubeError::WrongSize {
+                face_size: other.face_size,
+                expected: self.face_size,
+            });
         }
-        new_bottom.reverse();
-        for x in (0..self.face_size).rev() {
-            write_2d_vec(
-                &mut bottom_face,
-                &self.face_size,
-                &x,
-                &layer,
-                new_bottom.pop().unwrap(),
-            )
+        Ok(other.map(|id| self.stickers[self.sticker_index(id.face, id.x, id.y)]))
+    }
+
+    fn get_square(self, face: RubiksColor, x: usize, y: usize) -> Option<S> {
+        let t_face: &[S] = self.face(face);
+        let val: Option<&S> = t_face.get(x + y * self.face_size);
+        val.map(|v| v.to_owned())
+    }
+
+    // Same as get_square, but tells you why there is no square there
+    fn try_get_square(&self, face: RubiksColor, x: usize, y: usize) -> Result<S, CubeError> {
+        if x >= self.face_size || y >= self.face_size {
+            return Err(CubeError::InvalidCoordinate {
+                x,
+                y,
+                face_size: self.face_size,
+            });
         }
-        new_left.reverse();
-        for y in (0..self.face_size).rev() {
-            write_2d_vec(
-                &mut left_face,
-                &self.face_size,
-                &(self.face_size - 1 - layer),
-                &y,
-                new_left.pop().unwrap(),
-            )
+        Ok(self.stickers[self.sticker_index(face, x, y)])
+    }
+}
+
+impl RubiksCube<StickerId> {
+    // Initializes a new cube where every sticker is labelled with its starting position
+    // This is the identity element when cubes are treated as permutations
+    fn labelled(face_size: usize) -> Self {
+        Self::solved(face_size)
+    }
+
+    // Returns the state that undoes this one, so that composing the two gives back a solved cube
+    // If the sticker from position A is sitting at position B, the inverse moves B's sticker to A
+    fn inverse(&self) -> Self {
+        let mut inverse = self.clone();
+        for face in RubiksColor::ALL {
+            for y in 0..self.face_size {
+                for x in 0..self.face_size {
+                    let id = read_2d_vec(self.face(face), &self.face_size, &x, &y).unwrap();
+                    write_2d_vec(
+                        inverse.face_mut(id.face),
+                        &self.face_size,
+                        &id.x,
+                        &id.y,
+                        StickerId { face, x, y },
+                    );
+                }
+            }
         }
+        inverse.rehash();
+        inverse
+    }
 
-        // Step 6: Place new faces back onto cube
-        self.faces.insert(RubiksColor::UP, top_face);
-        self.faces.insert(RubiksColor::RIGHT, right_face);
-        self.faces.insert(RubiksColor::DOWN, bottom_face);
-        self.faces.insert(RubiksColor::LEFT, left_face);
+    // Shows the labelled cube the way it would actually look, with each sticker's original color
+    fn colors(&self) -> RubiksCube {
+        self.map(|id| id.face)
+    }
 
-        self
+    // Finds where the sticker that started at the given position has ended up
+    fn find_sticker(
+        &self,
+        face: RubiksColor,
+        x: usize,
+        y: usize,
+    ) -> Option<(RubiksColor, usize, usize)> {
+        let target = StickerId { face, x, y };
+        for face in RubiksColor::ALL {
+            for y in 0..self.face_size {
+                for x in 0..self.face_size {
+                    if *read_2d_vec(self.face(face), &self.face_size, &x, &y)? == target {
+                        return Some((face, x, y));
+                    }
+                }
+            }
+        }
+        None
     }
 
-    // Rotates the cube along the axis facing the right direction
-    fn rotate_top(mut self, direction: &TurnDirection, layer: &u8) -> RubiksCube {
-        // Step 1: Determine if the front face is being rotated, if so rotate it
-        if *layer == 0 {
-            let face = self
-                .faces
-                .get_mut(&RubiksColor::UP)
-                .unwrap() // We will never not have this be filled out so we can just unwrap here
-                .to_owned();
-            let new_face = match direction {
-                TurnDirection::CLOCKWISE => rotate_vec_cw(&face, &self.face_size),
-                TurnDirection::COUNTERCLOCKWISE => rotate_vec_ccw(&face, &self.face_size),
-            };
-            self.faces.insert(RubiksColor::UP, new_face);
+    // Finds which of the original stickers is currently sitting at the given position
+    fn original_at(&self, face: RubiksColor, x: usize, y: usize) -> Option<StickerId> {
+        if x >= self.face_size || y >= self.face_size {
+            return None;
         }
-        // Step 2: Determine if the back face is being rotated, if so rotate it
-        if *layer == self.face_size - 1 {
-            let face = self.faces.get_mut(&RubiksColor::DOWN).unwrap().to_owned();
-            let new_face = match direction {
-                TurnDirection::CLOCKWISE => rotate_vec_ccw(&face, &self.face_size),
-                TurnDirection::COUNTERCLOCKWISE => rotate_vec_cw(&face, &self.face_size),
-            };
-            self.faces.insert(RubiksColor::DOWN, new_face);
-        }
-        // Step 3: Determine the strips allong the top, bottom, left, and right that are going to
-        // be swapped
-        // Step 3.1: ALL faces itterate the same way here
-        let mut right_strip: Vec<RubiksColor> = vec![];
-        let mut right_face = self.faces.get_mut(&RubiksColor::RIGHT).unwrap().to_owned();
-        let mut left_strip: Vec<RubiksColor> = vec![];
-        let mut left_face = self.faces.get_mut(&RubiksColor::LEFT).unwrap().to_owned();
-        let mut front_strip: Vec<RubiksColor> = vec![];
-        let mut front_face = self
-            .faces
-            .get_mut(&RubiksColor::FORWARD)
-            .unwrap()
-            .to_owned();
-        let mut back_strip: Vec<RubiksColor> = vec![];
-        let mut back_face = self.faces.get_mut(&RubiksColor::BACK).unwrap().to_owned();
+        read_2d_vec(self.face(face), &self.face_size, &x, &y).copied()
+    }
 
-        for x in (0..self.face_size).rev() {
-            right_strip.push(
-                read_2d_vec(&right_face, &self.face_size, &x, &layer)
-                    .unwrap()
-                    .clone(),
+    // Lists every sticker that is not in its starting position along with where it is now
+    // Handy for checking exactly what a single move touched
+    fn moved_stickers(&self) -> Vec<(StickerId, (RubiksColor, usize, usize))> {
+        let mut moved = vec![];
+        for face in RubiksColor::ALL {
+            for y in 0..self.face_size {
+                for x in 0..self.face_size {
+                    let id = *read_2d_vec(self.face(face), &self.face_size, &x, &y).unwrap();
+                    if id != (StickerId { face, x, y }) {
+                        moved.push((id, (face, x, y)));
+                    }
+                }
+            }
+        }
+        moved
+    }
+}
+
+impl SuperCube {
+    // Finds how far the center sticker of a face is turned
+    // Only odd sized cubes have a single sticker in the middle of each face
+    fn center_orientation(&self, face: RubiksColor) -> Option<u8> {
+        if self.face_size.is_multiple_of(2) {
+            return None;
+        }
+        let middle = self.face_size / 2;
+        read_2d_vec(self.face(face), &self.face_size, &middle, &middle).map(|s| s.orientation)
+    }
+
+    // Checks that the cube could be put together from the pieces of a solved supercube
+    // Every sticker has to show up exactly once, and on odd sized cubes the fixed centers can only
+    // have been twisted by an odd number of quarter turns in total if the corners have gone through
+    // an odd permutation, since a face turn does both and no other move does either
+    // NOTE: This doesn't check that edges and corners are twisted in ways that are physically possible
+    fn is_valid(&self) -> bool {
+        // Step 1: Make sure every sticker is on the cube exactly once
+        let mut seen = HashSet::new();
+        for sticker in &self.stickers {
+            let on_cube = sticker.id.x < self.face_size && sticker.id.y < self.face_size;
+            if !on_cube || sticker.orientation > 3 || !seen.insert(sticker.id) {
+                return false;
+            }
+        }
+        if self.face_size < 3 || self.face_size.is_multiple_of(2) {
+            return true;
+        }
+        // Step 2: Add up how far the fixed centers have been twisted
+        let center_twist: u32 = RubiksColor::ALL
+            .iter()
+            .map(|face| u32::from(self.center_orientation(*face).unwrap()))
+            .sum();
+        // Step 3: Find the parity of the corner stickers' permutation, which matches the parity of
+        // the corner pieces' permutation since twisting a corner is an even 3 cycle of its stickers
+        let ends = [0, self.face_size - 1];
+        let mut corners = vec![];
+        for face in RubiksColor::ALL {
+            for y in ends {
+                for x in ends {
+                    corners.push(StickerId { face, x, y });
+                }
+            }
+        }
+        let mut permutation = vec![];
+        for corner in &corners {
+            let sticker = self.original_at(corner.face, corner.x, corner.y).unwrap();
+            match corners.iter().position(|c| *c == sticker.id) {
+                Some(i) => permutation.push(i),
+                None => return false,
+            }
+        }
+        let mut odd = false;
+        let mut visited = vec![false; permutation.len()];
+        for start in 0..permutation.len() {
+            let mut i = start;
+            while !visited[i] {
+                visited[i] = true;
+                i = permutation[i];
+                if i != start {
+                    odd = !odd;
+                }
+            }
+        }
+        (center_twist % 2 == 1) == odd
+    }
+
+    // Finds which sticker is currently sitting at the given position, and how it is turned
+    fn original_at(&self, face: RubiksColor, x: usize, y: usize) -> Option<SuperSticker> {
+        if x >= self.face_size || y >= self.face_size {
+            return None;
+        }
+        read_2d_vec(self.face(face), &self.face_size, &x, &y).copied()
+    }
+}
+
+fn main() {
+    let mut args: Vec<String> = std::env::args().skip(1).collect();
+    let result = match args.first().map(String::as_str) {
+        Some("bench") => bench::run(&args[1..]),
+        // Builds the solver's pattern databases ahead of time, so solving can just load them
+        Some("tables") => thread_option(&mut args).and_then(|threads| {
+            let dir = args
+                .get(1)
+                .map_or(solver::DEFAULT_TABLE_DIR, String::as_str);
+            solver::build_tables(dir, threads)
+        }),
+        Some("solve") => solve(&mut args),
+        Some("svg") => svg(&mut args),
+        Some("diagram") => diagram(&args),
+        Some("render") => render(&mut args),
+        Some("animate") => animate(&mut args),
+        Some("export") => export(&mut args),
+        Some("repl") => repl(&args),
+        Some("tui") => tui(&args),
+        _ => {
+            // Create a 3x3x3 cube, and turn the center layer facing the player upwards (ie, the
+            // second layer when viewed from the right)
+            let cube = RubiksCube::new(3).do_move(RubiksColor::RIGHT, 1, TurnDirection::CLOCKWISE);
+            let colors = net::ColorMode::detect();
+            println!(
+                "{}",
+                cube.color_net(net::ColorScheme::default(), colors)
+                    .fit_terminal()
             );
-            front_strip.push(
-                read_2d_vec(&front_face, &self.face_size, &x, &layer)
-                    .unwrap()
-                    .clone(),
+            Ok(())
+        }
+    };
+    if let Err(error) = result {
+        eprintln!("error: {error}");
+        std::process::exit(1);
+    }
+}
+
+// Pulls an option like `--threads 4` out of the arguments, if it's there
+fn take_option<T: std::str::FromStr>(
+    args: &mut Vec<String>,
+    name: &str,
+    what: &str,
+) -> Result<Option<T>, CubeError> {
+    let Some(position) = args.iter().position(|arg| arg == name) else {
+        return Ok(None);
+    };
+    let value = args.get(position + 1).map_or("", String::as_str);
+    let parsed = value
+        .parse()
+        .map_err(|_| CubeError::Parse(format!("\"{value}\" as {what}")))?;
+    args.drain(position..position + 2);
+    Ok(Some(parsed))
+}
+
+// The `--threads N` option, using every core if there isn't one
+fn thread_option(args: &mut Vec<String>) -> Result<usize, CubeError> {
+    match take_option(args, "--threads", "a thread count")? {
+        Some(0) => Err(CubeError::Parse("\"0\" as a thread count".to_string())),
+        Some(threads) => Ok(threads),
+        None => Ok(parallel::default_threads()),
+    }
+}
+
+// Scrambles a 3x3 with the moves given and prints a solution for it, or solves the cube given as
+// a facelet string with `--facelets URFDLB...`
+// With `--length N` it keeps looking until it finds a solution at most N moves long, and
+// `--time SECONDS` or `--nodes N` stop it early with the best so far
+fn solve(args: &mut Vec<String>) -> Result<(), CubeError> {
+    let threads = thread_option(args)?;
+    let facelets = take_option::<String>(args, "--facelets", "a facelet string")?;
+    let time_limit = take_option::<f64>(args, "--time", "a number of seconds")?
+        .map(|seconds| {
+            std::time::Duration::try_from_secs_f64(seconds)
+                .map_err(|_| CubeError::Parse(format!("\"{seconds}\" as a number of seconds")))
+        })
+        .transpose()?;
+    let options = solver::SolveOptions {
+        max_length: take_option(args, "--length", "a move count")?,
+        time_limit,
+        node_limit: take_option(args, "--nodes", "a number of positions")?,
+        cancel: None,
+    };
+    let cube = match facelets {
+        Some(facelets) => RubiksCube::from_facelets(&facelets)?,
+        None => RubiksCube::new(3).try_do_algorithm(&args[1..].join(" ").parse()?)?,
+    };
+    let solver = solver::Solver::load_or_new(solver::DEFAULT_TABLE_DIR)?.with_threads(threads);
+    // Only searches that keep improving run long enough to be worth following, and the
+    // progress goes to stderr so the solution can still be piped somewhere on its own
+    let follow = options.max_length.is_some();
+    let solution = solver.solve_with(&cube, &options, &|progress| {
+        if follow {
+            let best = progress
+                .best
+                .as_ref()
+                .map_or("none yet".to_string(), |best| {
+                    format!("{} moves", best.moves.len())
+                });
+            eprintln!(
+                "{:.1}s: depth {}, {} positions, best {best}",
+                progress.elapsed.as_secs_f64(),
+                progress.depth,
+                progress.nodes
             );
-            left_strip.push(
-                read_2d_vec(&front_face, &self.face_size, &x, &layer)
-                    .unwrap()
-                    .clone(),
+        }
+    })?;
+    println!("{solution}");
+    Ok(())
+}
+
+// Prints an SVG picture of a cube after the moves given, `svg iso` for the 3D view instead of the
+// net, and `--size N` for a cube other than a 3x3
+fn svg(args: &mut Vec<String>) -> Result<(), CubeError> {
+    let size = take_option(args, "--size", "a cube size")?.unwrap_or(3);
+    let (view, moves) = match args.get(1).map(String::as_str) {
+        Some("iso") => (svg::SvgView::Isometric, &args[2..]),
+        Some("net") => (svg::SvgView::Net, &args[2..]),
+        _ => (svg::SvgView::Net, &args[1..]),
+    };
+    let cube = RubiksCube::try_new(size)?.try_do_algorithm(&moves.join(" ").parse()?)?;
+    let options = svg::SvgOptions {
+        view,
+        ..Default::default()
+    };
+    print!("{}", cube.to_svg(&options));
+    Ok(())
+}
+
+// Prints an algorithm sheet picture of the case the moves given solve
+// `diagram oll`, `pll` or `ll` look down on the last layer, and `diagram f2l` shows the F2L slot
+fn diagram(args: &[String]) -> Result<(), CubeError> {
+    let kind = args.get(1).map_or("", String::as_str);
+    let (stage, arrows) = match kind {
+        "ll" => (diagram::Stage::Ll, false),
+        "oll" => (diagram::Stage::Oll, false),
+        "pll" => (diagram::Stage::Ll, true),
+        "f2l" => (diagram::Stage::F2l, false),
+        _ => return Err(CubeError::Parse(format!("\"{kind}\" as a kind of diagram"))),
+    };
+    let case: notation::Algorithm = args[2..].join(" ").parse()?;
+    let diagram = diagram::Diagram {
+        stage,
+        arrows,
+        ..Default::default()
+    };
+    let svg = if kind == "f2l" {
+        diagram.f2l(&case)?
+    } else {
+        diagram.last_layer(&case)?
+    };
+    print!("{svg}");
+    Ok(())
+}
+
+// Renders a cube after the moves given to a PNG or PPM file, `render cube.png R U R'`
+// `--size N` renders a cube other than a 3x3, and `--width N` and `--height N` set the picture's
+// size in pixels
+fn render(args: &mut Vec<String>) -> Result<(), CubeError> {
+    let size = take_option(args, "--size", "a cube size")?.unwrap_or(3);
+    let mut options = render::RenderOptions::default();
+    picture_size_options(args, &mut options)?;
+    let path = args
+        .get(1)
+        .ok_or_else(|| CubeError::Parse("nothing as an image file name".to_string()))?;
+    let cube = RubiksCube::try_new(size)?.try_do_algorithm(&args[2..].join(" ").parse()?)?;
+    cube.render(&options).save(path)
+}
+
+// Animates the moves given being made on a solved cube, `animate r-u.svg R U` for an animated
+// SVG or `animate frames R U` for a directory of numbered PNG frames
+// `--size N` animates a cube other than a 3x3, `--frames N` sets how many frames each move takes
+// and `--width N` and `--height N` set the size of each frame in pixels
+fn animate(args: &mut Vec<String>) -> Result<(), CubeError> {
+    let size = take_option(args, "--size", "a cube size")?.unwrap_or(3);
+    let mut animation = animation::Animation::default();
+    animation.frames_per_move =
+        take_option(args, "--frames", "a number of frames")?.unwrap_or(animation.frames_per_move);
+    picture_size_options(args, &mut animation.render)?;
+    let path = args
+        .get(1)
+        .ok_or_else(|| CubeError::Parse("nothing as an output path".to_string()))?;
+    let cube = RubiksCube::try_new(size)?;
+    let algorithm = args[2..].join(" ").parse()?;
+    if path.to_ascii_lowercase().ends_with(".svg") {
+        std::fs::write(path, animation.to_svg(&cube, &algorithm)?)?;
+    } else {
+        let frames = animation.save_frames(&cube, &algorithm, path, "png")?;
+        println!("{frames} frames written to {path}");
+    }
+    Ok(())
+}
+
+// The `--width N` and `--height N` options, kept to sizes that can be drawn without running out
+// of memory
+fn picture_size_options(
+    args: &mut Vec<String>,
+    options: &mut render::RenderOptions,
+) -> Result<(), CubeError> {
+    for (name, what, size) in [
+        ("--width", "a width", &mut options.width),
+        ("--height", "a height", &mut options.height),
+    ] {
+        match take_option(args, name, what)? {
+            Some(0) => return Err(CubeError::Parse(format!("\"0\" as {what}"))),
+            Some(pixels) if pixels > render::MAX_IMAGE_SIZE => {
+                return Err(CubeError::TooLarge(format!(
+                    "a picture {pixels} pixels across"
+                )))
+            }
+            Some(pixels) => *size = pixels,
+            None => {}
+        }
+    }
+    Ok(())
+}
+
+// Exports a cube after the moves given as a 3D model, `export cube.obj R U` writes cube.obj and
+// cube.mtl, and `export cube.gltf R U` writes a single glTF file
+// `--size N` exports a cube other than a 3x3
+fn export(args: &mut Vec<String>) -> Result<(), CubeError> {
+    let size = take_option(args, "--size", "a cube size")?.unwrap_or(3);
+    let path = args
+        .get(1)
+        .ok_or_else(|| CubeError::Parse("nothing as a model file name".to_string()))?;
+    let cube = RubiksCube::try_new(size)?.try_do_algorithm(&args[2..].join(" ").parse()?)?;
+    cube.mesh(&mesh::MeshOptions::default()).save(path)
+}
+
+// Turns a cube by typing moves and commands, starting from a 3x3 or `repl N` for an NxN
+fn repl(args: &[String]) -> Result<(), CubeError> {
+    repl::Repl::new(size_argument(args)?)?.run(std::io::stdin().lock(), &mut std::io::stdout())
+}
+
+// A full screen virtual cube turned with cstimer's keys, a 3x3 or `tui N` for an NxN
+fn tui(args: &[String]) -> Result<(), CubeError> {
+    tui::run(size_argument(args)?)
+}
+
+// The cube size given after a command, 3 if there isn't one
+fn size_argument(args: &[String]) -> Result<usize, CubeError> {
+    match args.get(1) {
+        Some(size) => size
+            .parse()
+            .map_err(|_| CubeError::Parse(format!("\"{size}\" as a cube size"))),
+        None => Ok(3),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Sizes that used to overflow u8 arithmetic, along with the smallest cube
+    const SIZES: [usize; 4] = [1, 16, 17, 100];
+
+    // A handful of layers from each end and the middle, so large cubes don't take forever
+    fn layers(size: usize) -> Vec<usize> {
+        let mut layers = vec![0, 1, size / 2, size.saturating_sub(2), size - 1];
+        layers.retain(|layer| *layer < size);
+        layers.sort();
+        layers.dedup();
+        layers
+    }
+
+    #[test]
+    fn new_cube_has_full_faces() {
+        for size in SIZES {
+            let cube = RubiksCube::new(size);
+            assert_eq!(cube.face_size, size);
+            for face in RubiksColor::ALL {
+                assert_eq!(cube.face(face).len(), size * size);
+                assert!(cube.face(face).iter().all(|s| *s == face));
+            }
+            assert_eq!(
+                cube.clone()
+                    .get_square(RubiksColor::BACK, size - 1, size - 1),
+                Some(RubiksColor::BACK)
             );
-            back_strip.push(
-                read_2d_vec(&back_face, &self.face_size, &x, &layer)
+            assert!(cube.is_solved());
+        }
+    }
+
+    #[test]
+    fn oversized_requests_are_refused() {
+        assert_eq!(RubiksCube::try_new(0), Err(CubeError::InvalidSize(0)));
+        assert_eq!(
+            RubiksCube::try_new(1000).unwrap().stickers.len(),
+            MAX_STICKERS
+        );
+        // Sizes so big that counting their stickers overflows are refused the same way
+        for size in [1001, 100000, 1 << 32, usize::MAX] {
+            assert_eq!(
+                RubiksCube::try_new(size),
+                Err(CubeError::TooLarge(format!("a {size}x{size} cube")))
+            );
+        }
+        let mut options = render::RenderOptions::default();
+        for option in ["--width", "--height"] {
+            let mut args = ["render", option, "100000"].map(String::from).to_vec();
+            assert!(matches!(
+                picture_size_options(&mut args, &mut options),
+                Err(CubeError::TooLarge(_))
+            ));
+        }
+        let mut args = ["render", "--width", "0"].map(String::from).to_vec();
+        assert!(picture_size_options(&mut args, &mut options).is_err());
+        let mut args = ["render", "--width", "200", "--height", "300"]
+            .map(String::from)
+            .to_vec();
+        picture_size_options(&mut args, &mut options).unwrap();
+        assert_eq!((options.width, options.height, args.len()), (200, 300, 1));
+    }
+
+    #[test]
+    fn moves_undo_at_large_sizes() {
+        for size in SIZES {
+            for face in RubiksColor::ALL {
+                for layer in layers(size) {
+                    let mut cube = RubiksCube::labelled(size);
+                    let turned = cube.do_move(face, layer, TurnDirection::CLOCKWISE);
+                    assert!(size == 1 || !turned.is_solved());
+                    let undone =
+                        turned
+                            .clone()
+                            .do_move(face, layer, TurnDirection::COUNTERCLOCKWISE);
+                    assert!(undone.is_solved());
+                    assert!(turned.compose(&turned.inverse()).is_solved());
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn composing_follows_moves() {
+        let first: notation::Algorithm = "R U2 F' D L2 B".parse().unwrap();
+        let second: notation::Algorithm = "U' R2 B L' F2 D".parse().unwrap();
+        for size in [2, 3, 4] {
+            let moved = |algorithm| {
+                RubiksCube::labelled(size)
+                    .try_do_algorithm(algorithm)
                     .unwrap()
-                    .clone(),
+            };
+            let (a, b) = (moved(&first), moved(&second));
+            let both = notation::Algorithm {
+                moves: [first.moves.clone(), second.moves.clone()].concat(),
+            };
+            assert_eq!(a.compose(&b).stickers, moved(&both).stickers);
+            assert_eq!(a.compose(&b).zobrist_hash(), moved(&both).zobrist_hash());
+            // A state and its inverse undo each other in either order
+            assert!(a.compose(&a.inverse()).is_solved());
+            assert!(a.inverse().compose(&a).is_solved());
+            assert_eq!(a.inverse().stickers, moved(&first.inverse()).stickers);
+            assert_eq!(
+                a.try_compose(&RubiksCube::labelled(size + 1)),
+                Err(CubeError::WrongSize {
+                    face_size: size + 1,
+                    expected: size
+                })
             );
         }
+    }
 
-        // Step 4: Swap the faces forwards if clockwise, backwards if counterclockwise
-        let mut new_left: Vec<RubiksColor>;
-        let mut new_back: Vec<RubiksColor>;
-        let mut new_right: Vec<RubiksColor>;
-        let mut new_front: Vec<RubiksColor>;
+    #[test]
+    fn symmetries_form_a_group() {
+        use symmetry::Symmetry;
+        let all = Symmetry::all();
+        assert_eq!(all.len(), 48);
+        assert_eq!(all.iter().collect::<HashSet<_>>().len(), 48);
+        assert_eq!(all[0], Symmetry::IDENTITY);
+        assert_eq!(Symmetry::rotations().len(), 24);
+        for s in &all {
+            assert_eq!(s.after(&s.inverse()), Symmetry::IDENTITY);
+            assert_eq!(s.inverse().after(s), Symmetry::IDENTITY);
+            for t in &all {
+                assert!(all.contains(&s.after(t)));
+            }
+        }
+    }
 
-        match direction {
-            TurnDirection::CLOCKWISE => {
-                new_left = front_strip;
-                new_back = left_strip;
-                new_right = back_strip;
-                new_front = right_strip;
-            }
-            TurnDirection::COUNTERCLOCKWISE => {
-                new_left = back_strip;
-                new_back = right_strip;
-                new_right = front_strip;
-                new_front = left_strip;
+    #[test]
+    fn conjugating_matches_conjugated_moves() {
+        let scramble: notation::Algorithm = "R U2 F' D L2 B U'".parse().unwrap();
+        for size in [2, 3, 4] {
+            let colors = RubiksCube::new(size).try_do_algorithm(&scramble).unwrap();
+            let labelled = RubiksCube::labelled(size)
+                .try_do_algorithm(&scramble)
+                .unwrap();
+            for s in symmetry::Symmetry::all() {
+                let moves = notation::Algorithm {
+                    moves: scramble.moves.iter().map(|m| s.apply_move(m)).collect(),
+                };
+                let expected = RubiksCube::new(size).try_do_algorithm(&moves).unwrap();
+                assert_eq!(colors.conjugate(&s).stickers, expected.stickers, "{s:?}");
+                let expected = RubiksCube::labelled(size).try_do_algorithm(&moves).unwrap();
+                assert_eq!(labelled.conjugate(&s).stickers, expected.stickers, "{s:?}");
             }
-        };
-        // Step 5: Write new strips to new faces
-        new_left.reverse();
-        new_back.reverse();
-        new_right.reverse();
-        new_front.reverse();
-        for x in (0..self.face_size).rev() {
-            write_2d_vec(
-                &mut front_face,
-                &self.face_size,
-                &x,
-                &(self.face_size - 1 - layer),
-                new_front.pop().unwrap(),
+        }
+    }
+
+    #[test]
+    fn canonical_forms_agree_across_symmetries() {
+        assert_eq!(RubiksCube::new(3).stabilizer().len(), 48);
+        assert_eq!(RubiksCube::labelled(3).stabilizer().len(), 48);
+        // A half turn of every face keeps more symmetry than a scramble does
+        let checkerboard = RubiksCube::new(3)
+            .try_do_algorithm(&"R2 L2 U2 D2 F2 B2".parse().unwrap())
+            .unwrap();
+        assert_eq!(checkerboard.stabilizer().len(), 48);
+        let scrambled = RubiksCube::new(3)
+            .try_do_algorithm(&"R U2 F' D L2 B U'".parse().unwrap())
+            .unwrap();
+        assert_eq!(scrambled.stabilizer(), vec![symmetry::Symmetry::IDENTITY]);
+        let (canonical, s) = scrambled.canonical();
+        assert_eq!(scrambled.conjugate(&s).stickers, canonical.stickers);
+        for s in symmetry::Symmetry::all() {
+            let (other, _) = scrambled.conjugate(&s).canonical();
+            assert_eq!(other.stickers, canonical.stickers, "{s:?}");
+        }
+    }
+
+    #[test]
+    fn notation_parses() {
+        use notation::{Algorithm, Move};
+        use TurnDirection::*;
+        let cases = [
+            ("R", RubiksColor::RIGHT, 0, CLOCKWISE, false),
+            ("U'", RubiksColor::UP, 0, COUNTERCLOCKWISE, false),
+            ("F2", RubiksColor::FORWARD, 0, CLOCKWISE, true),
+            ("B2'", RubiksColor::BACK, 0, COUNTERCLOCKWISE, true),
+            ("2L", RubiksColor::LEFT, 1, CLOCKWISE, false),
+            ("17D'", RubiksColor::DOWN, 16, COUNTERCLOCKWISE, false),
+        ];
+        for (text, face, layer, direction, double) in cases {
+            let m = Move {
+                face,
+                layer,
+                direction,
+                double,
+            };
+            assert_eq!(text.parse(), Ok(m), "{text}");
+            assert_eq!(m.to_string(), text);
+        }
+        let algorithm: Algorithm = "  R U R'\tU'\n".parse().unwrap();
+        assert_eq!(algorithm.to_string(), "R U R' U'");
+        assert_eq!("".parse(), Ok(Algorithm::default()));
+    }
+
+    #[test]
+    fn bad_notation_is_rejected() {
+        use notation::{Algorithm, Move};
+        let cases = [
+            "",                      // Nothing at all
+            "X",                     // Not a face
+            "r",                     // Faces are capitals
+            "R3",                    // Not a quarter or half turn
+            "R''",                   // Turned back twice
+            "R2 ",                   // Whitespace belongs between moves
+            "0R",                    // Layers count from 1
+            "2",                     // A layer with no face
+            "99999999999999999999R", // Too many layers to count
+        ];
+        for text in cases {
+            assert!(
+                matches!(text.parse::<Move>(), Err(CubeError::Parse(_))),
+                "{text:?}"
             );
-            write_2d_vec(
-                &mut left_face,
-                &self.face_size,
-                &x,
-                &(self.face_size - 1 - layer),
-                new_left.pop().unwrap(),
+        }
+        assert!("R U X".parse::<Algorithm>().is_err());
+        // Layers the cube doesn't have parse, but can't be played
+        let algorithm: Algorithm = "R 4R".parse().unwrap();
+        assert_eq!(
+            RubiksCube::new(3).try_do_algorithm(&algorithm),
+            Err(CubeError::InvalidLayer {
+                layer: 3,
+                face_size: 3
+            })
+        );
+    }
+
+    #[test]
+    fn facelets_are_checked() {
+        let solved = RubiksCube::new(3).to_facelets();
+        assert_eq!(
+            solved,
+            "UUUUUUUUURRRRRRRRRFFFFFFFFFDDDDDDDDDLLLLLLLLLBBBBBBBBB"
+        );
+        let scrambled = RubiksCube::new(4)
+            .try_do_algorithm(&"R U2 2F' D L2 B".parse().unwrap())
+            .unwrap();
+        assert_eq!(
+            RubiksCube::from_facelets(&scrambled.to_facelets()),
+            Ok(scrambled)
+        );
+        assert_eq!(
+            RubiksCube::from_facelets("U R F D L B"),
+            Ok(RubiksCube::new(1))
+        );
+        let mut too_many_ups = solved.clone();
+        too_many_ups.replace_range(9..10, "U");
+        let mut lowercase = solved.clone();
+        lowercase.replace_range(0..1, "u");
+        let cases = [
+            ("", "nothing"),
+            (&solved[1..], "one facelet short"),
+            (&solved[..50], "not a multiple of six"),
+            (&too_many_ups, "the wrong number of each color"),
+            (&lowercase, "a letter that isn't a face"),
+            ("UUUURRRRFFFFDDDDLLLLBBBBX", "a letter that isn't a face"),
+        ];
+        for (facelets, why) in cases {
+            assert!(RubiksCube::from_facelets(facelets).is_err(), "{why}");
+        }
+    }
+
+    #[test]
+    fn four_quarter_turns_are_identity() {
+        for size in SIZES {
+            for face in RubiksColor::ALL {
+                for layer in layers(size) {
+                    let mut cube = RubiksCube::labelled(size);
+                    for _ in 0..4 {
+                        cube = cube.do_move(face, layer, TurnDirection::CLOCKWISE);
+                    }
+                    assert!(cube.is_solved());
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn far_stickers_are_tracked() {
+        for size in SIZES {
+            let mut cube = RubiksCube::labelled(size);
+            let turned = cube.do_move(RubiksColor::RIGHT, 0, TurnDirection::CLOCKWISE);
+            // The front face's right column moves up onto the top face
+            assert_eq!(
+                turned.find_sticker(RubiksColor::FORWARD, size - 1, size - 1),
+                Some((RubiksColor::UP, size - 1, size - 1))
             );
-            write_2d_vec(
-                &mut back_face,
-                &self.face_size,
-                &x,
-                &(self.face_size - 1 - layer),
-                new_back.pop().unwrap(),
+            assert_eq!(
+                turned.original_at(RubiksColor::UP, size - 1, 0),
+                Some(StickerId {
+                    face: RubiksColor::FORWARD,
+                    x: size - 1,
+                    y: 0
+                })
             );
-            write_2d_vec(
-                &mut right_face,
-                &self.face_size,
-                &x,
-                &(self.face_size - 1 - layer),
-                new_right.pop().unwrap(),
+        }
+    }
+
+    #[test]
+    fn one_move_moves_exactly_its_stickers() {
+        use RubiksColor::*;
+        let id = |face, x, y| StickerId { face, x, y };
+        let turned = RubiksCube::labelled(2).do_move(UP, 0, TurnDirection::CLOCKWISE);
+        // The up face spins a quarter turn clockwise, and the top row of each side moves on to the
+        // side to its left
+        let mut expected = vec![
+            (id(UP, 0, 0), (UP, 1, 0)),
+            (id(UP, 1, 0), (UP, 1, 1)),
+            (id(UP, 1, 1), (UP, 0, 1)),
+            (id(UP, 0, 1), (UP, 0, 0)),
+        ];
+        for (from, to) in [
+            (FORWARD, LEFT),
+            (LEFT, BACK),
+            (BACK, RIGHT),
+            (RIGHT, FORWARD),
+        ] {
+            for x in 0..2 {
+                expected.push((id(from, x, 0), (to, x, 0)));
+            }
+        }
+        let moved = turned.moved_stickers();
+        assert_eq!(moved.len(), expected.len());
+        assert_eq!(
+            moved.into_iter().collect::<HashSet<_>>(),
+            expected.iter().copied().collect()
+        );
+        for (sticker, (face, x, y)) in expected {
+            assert_eq!(
+                turned.find_sticker(sticker.face, sticker.x, sticker.y),
+                Some((face, x, y))
             );
+            assert_eq!(turned.original_at(face, x, y), Some(sticker));
+        }
+        // Looked at by color, a labelled cube is the same as a plain one turned the same way
+        for size in [2, 3, 4] {
+            for face in RubiksColor::ALL {
+                for layer in 0..size {
+                    let plain =
+                        RubiksCube::new(size).do_move(face, layer, TurnDirection::CLOCKWISE);
+                    let labelled =
+                        RubiksCube::labelled(size).do_move(face, layer, TurnDirection::CLOCKWISE);
+                    assert_eq!(labelled.colors(), plain);
+                }
+            }
         }
+    }
 
-        self.faces.insert(RubiksColor::RIGHT, right_face);
-        self.faces.insert(RubiksColor::FORWARD, front_face);
-        self.faces.insert(RubiksColor::LEFT, left_face);
-        self.faces.insert(RubiksColor::BACK, back_face);
+    #[test]
+    fn supercube_centers_twist() {
+        use RubiksColor::*;
+        let mut cube = SuperCube::solved(3);
+        for quarter_turns in 1..=4 {
+            cube.apply_move(UP, 0, TurnDirection::CLOCKWISE);
+            assert_eq!(cube.center_orientation(UP), Some(quarter_turns % 4));
+            for face in [DOWN, LEFT, RIGHT, FORWARD, BACK] {
+                assert_eq!(cube.center_orientation(face), Some(0));
+            }
+        }
+        assert!(cube.is_solved());
+        assert_eq!(SuperCube::solved(4).center_orientation(UP), None);
+        // Twists the up center half way round and puts every sticker back where it started
+        let twisted = SuperCube::solved(3)
+            .try_do_algorithm(&"U R L U2 R' L' U R L U2 R' L'".parse().unwrap())
+            .unwrap();
+        assert_eq!(twisted.map(|s| s.id.face), RubiksCube::new(3));
+        assert_eq!(twisted.center_orientation(UP), Some(2));
+        assert!(!twisted.is_solved());
+        assert!(twisted.is_valid());
+    }
 
-        self
+    #[test]
+    fn supercube_validity() {
+        // A single center a quarter turn out can't be reached by turning the cube
+        let mut cube = SuperCube::solved(3);
+        cube.face_mut(RubiksColor::UP)[4].orientation = 1;
+        cube.rehash();
+        assert!(!cube.is_valid());
+        // Neither can a cube with a sticker on it twice
+        let mut cube = SuperCube::solved(3);
+        cube.face_mut(RubiksColor::UP)[0] = cube.face(RubiksColor::UP)[1];
+        cube.rehash();
+        assert!(!cube.is_valid());
+        // Anything turning does get to is fine, middle layers and all
+        let mut scrambler = scramble::Scrambler::with_seed(28);
+        for size in [1, 2, 3, 4, 5] {
+            for _ in 0..10 {
+                let scramble = scrambler.scramble(size);
+                let mut cube = SuperCube::solved(size).try_do_algorithm(&scramble).unwrap();
+                cube.apply_move(RubiksColor::RIGHT, size / 2, TurnDirection::CLOCKWISE);
+                cube.apply_move(
+                    RubiksColor::FORWARD,
+                    size / 2,
+                    TurnDirection::COUNTERCLOCKWISE,
+                );
+                assert!(cube.is_valid(), "{size}x{size} {scramble}");
+            }
+        }
     }
-    // Rotates the cube along the axis facing the right direction
-    fn rotate_right(mut self, direction: &TurnDirection, layer: &u8) -> RubiksCube {
-        // Step 1: Determine if the front face is being rotated, if so rotate it
-        if *layer == 0 {
-            let face = self
-                .faces
-                .get_mut(&RubiksColor::RIGHT)
-                .unwrap() // We will never not have this be filled out so we can just unwrap here
-                .to_owned();
-            let new_face = match direction {
-                TurnDirection::CLOCKWISE => rotate_vec_cw(&face, &self.face_size),
-                TurnDirection::COUNTERCLOCKWISE => rotate_vec_ccw(&face, &self.face_size),
+
+    #[test]
+    fn packed_cubes_round_trip() {
+        let mut scrambler = scramble::Scrambler::with_seed(32);
+        for size in [1, 2, 3] {
+            for _ in 0..10 {
+                let cube = RubiksCube::new(size)
+                    .try_do_algorithm(&scrambler.scramble(size))
+                    .unwrap();
+                let packed = packed::PackedCube::try_from(&cube).unwrap();
+                assert_eq!(packed.face_size(), size);
+                assert_eq!(RubiksCube::from(packed), cube);
+                // Packing loses nothing, so different cubes stay different
+                let turned = cube
+                    .clone()
+                    .do_move(RubiksColor::UP, 0, TurnDirection::CLOCKWISE);
+                assert_eq!(
+                    packed == packed::PackedCube::try_from(&turned).unwrap(),
+                    cube == turned
+                );
+            }
+        }
+        let empty = RubiksCube::from_stickers(0, vec![]);
+        assert_eq!(
+            packed::PackedCube::try_from(&empty),
+            Err(CubeError::InvalidSize(0))
+        );
+        assert_eq!(
+            packed::PackedCube::try_from(&RubiksCube::new(packed::MAX_PACKED_SIZE + 1)),
+            Err(CubeError::TooLarge("a packed 4x4 cube".to_string()))
+        );
+    }
+
+    #[test]
+    fn fixed_cubes_turn_like_cubes() {
+        use fixed::{Cube2, Cube3, FixedCube};
+        // Every move on its own, and then all of them one after another
+        let mut cube = RubiksCube::labelled(3);
+        let mut fixed = FixedCube::<3, StickerId>::solved();
+        for direction in [TurnDirection::CLOCKWISE, TurnDirection::COUNTERCLOCKWISE] {
+            for face in RubiksColor::ALL {
+                for layer in 0..3 {
+                    let turned =
+                        FixedCube::<3, StickerId>::solved().do_move(face, layer, direction);
+                    let expected = RubiksCube::labelled(3).do_move(face, layer, direction);
+                    assert_eq!(RubiksCube::from(turned), expected);
+                    cube.apply_move(face, layer, direction);
+                    fixed.apply_move(face, layer, direction);
+                    assert_eq!(RubiksCube::from(fixed), cube);
+                }
+            }
+        }
+        // Every layer of a face together turns the whole cube, which the opposite face undoes
+        assert!(fixed.is_solved() && cube.is_solved());
+        assert!(!fixed
+            .do_move(RubiksColor::RIGHT, 0, TurnDirection::CLOCKWISE)
+            .is_solved());
+        cube.apply_move(RubiksColor::RIGHT, 0, TurnDirection::CLOCKWISE);
+        fixed.apply_move(RubiksColor::RIGHT, 0, TurnDirection::CLOCKWISE);
+        assert_eq!(
+            fixed.get_square(RubiksColor::UP, 2, 1),
+            cube.try_get_square(RubiksColor::UP, 2, 1).ok()
+        );
+        assert_eq!(fixed.get_square(RubiksColor::UP, 3, 1), None);
+        // Going back and forth between the two kinds keeps every sticker
+        assert_eq!(FixedCube::<3, StickerId>::try_from(&cube), Ok(fixed));
+        let scrambled = RubiksCube::new(2)
+            .try_do_algorithm(&"R U2 F' R2".parse().unwrap())
+            .unwrap();
+        let small = Cube2::try_from(&scrambled).unwrap();
+        assert_eq!(RubiksCube::from(small), scrambled);
+        assert!(Cube3::new().is_solved());
+        // Sizes have to match, and layers have to be on the cube
+        assert_eq!(
+            Cube3::try_from(&scrambled),
+            Err(CubeError::WrongSize {
+                face_size: 2,
+                expected: 3
+            })
+        );
+        assert_eq!(
+            Cube2::new().try_apply_move(RubiksColor::UP, 2, TurnDirection::CLOCKWISE),
+            Err(CubeError::InvalidLayer {
+                layer: 2,
+                face_size: 2
+            })
+        );
+    }
+
+    // Turns every face and layer picked by layers() both ways, checking the hash after each move
+    fn check_hash_through_moves<S: Sticker>(size: usize) {
+        let mut cube = RubiksCube::<S>::solved(size);
+        for face in RubiksColor::ALL {
+            for layer in layers(size) {
+                for direction in [TurnDirection::CLOCKWISE, TurnDirection::COUNTERCLOCKWISE] {
+                    cube.apply_move(face, layer, direction);
+                    assert_eq!(cube.zobrist_hash(), RubiksCube::full_hash(&cube.stickers));
+                    cube.apply_move(face, layer, TurnDirection::CLOCKWISE);
+                    assert_eq!(cube.zobrist_hash(), RubiksCube::full_hash(&cube.stickers));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn hash_matches_full_recompute() {
+        for size in SIZES {
+            check_hash_through_moves::<RubiksColor>(size);
+            check_hash_through_moves::<StickerId>(size);
+            check_hash_through_moves::<SuperSticker>(size);
+        }
+    }
+
+    #[test]
+    fn table_moves_keep_hash() {
+        for size in [2, 3, 4] {
+            let table = move_table::MoveTable::new(size).unwrap();
+            let mut cube = SuperCube::solved(size);
+            for face in RubiksColor::ALL {
+                for layer in 0..size {
+                    table
+                        .try_apply_move(&mut cube, face, layer, TurnDirection::CLOCKWISE)
+                        .unwrap();
+                    assert_eq!(cube.zobrist_hash(), SuperCube::full_hash(&cube.stickers));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn bench_runs_at_its_default_sizes() {
+        // Cubes too big for a move table are still timed, just without one
+        for size in bench::DEFAULT_SIZES {
+            RubiksCube::try_new(size).unwrap();
+            let table = bench::table(size).unwrap();
+            assert_eq!(table.is_some(), size <= move_table::MAX_TABLE_SIZE);
+        }
+    }
+
+    #[test]
+    fn move_table_files_are_checked() {
+        let table = move_table::MoveTable::new(3).unwrap();
+        let mut bytes = vec![];
+        table.write_to(&mut bytes).unwrap();
+        let read = |bytes: &[u8]| move_table::MoveTable::read_from(&mut &bytes[..]);
+        assert_eq!(read(&bytes), Ok(table));
+        // Cut short anywhere, the file is missing something
+        for length in [0, 4, 9, bytes.len() / 2, bytes.len() - 1] {
+            assert!(read(&bytes[..length]).is_err(), "{length} bytes");
+        }
+        // A header asking for a huge cube is turned down before anything is allocated for it
+        let mut huge = b"RCMT\x01".to_vec();
+        huge.extend_from_slice(&0x0010_0000u32.to_le_bytes());
+        assert!(matches!(read(&huge), Err(CubeError::TooLarge(_))));
+        huge[5..9].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(matches!(read(&huge), Err(CubeError::TooLarge(_))));
+    }
+
+    #[test]
+    fn same_state_same_hash() {
+        let solved = RubiksCube::new(3);
+        let mut cube = solved.clone();
+        cube.apply_move(RubiksColor::RIGHT, 0, TurnDirection::CLOCKWISE);
+        assert_ne!(cube.zobrist_hash(), solved.zobrist_hash());
+        cube.apply_move(RubiksColor::LEFT, 2, TurnDirection::CLOCKWISE);
+        cube.apply_move(RubiksColor::UP, 0, TurnDirection::CLOCKWISE);
+        cube.apply_move(RubiksColor::UP, 0, TurnDirection::COUNTERCLOCKWISE);
+        assert_eq!(cube, solved);
+        assert_eq!(cube.zobrist_hash(), solved.zobrist_hash());
+        // Middle slices turned from opposite faces pair up, leaving six different states
+        let mut hashes = HashSet::new();
+        for face in RubiksColor::ALL {
+            for direction in [TurnDirection::CLOCKWISE, TurnDirection::COUNTERCLOCKWISE] {
+                let turned = solved.clone().do_move(face, 1, direction);
+                hashes.insert(turned.zobrist_hash());
+            }
+        }
+        assert_eq!(hashes.len(), 6);
+    }
+
+    fn assert_near(a: [f64; 3], b: [f64; 3]) {
+        assert!((0..3).all(|i| (a[i] - b[i]).abs() < 1e-9), "{a:?} != {b:?}");
+    }
+
+    #[test]
+    fn sticker_geometry_round_trips() {
+        for size in SIZES {
+            for face in RubiksColor::ALL {
+                for y in 0..size {
+                    for x in 0..size {
+                        let id = StickerId { face, x, y };
+                        let geometry = id.geometry(size).unwrap();
+                        // Every sticker is on the surface of its face, facing straight out
+                        assert_near(geometry.normal, geometry::face_normal(face));
+                        let out = (0..3)
+                            .map(|i| geometry.center[i] * geometry.normal[i])
+                            .sum::<f64>();
+                        assert!((out - 1.0).abs() < 1e-9);
+                        assert_eq!(StickerId::at_point(geometry.center, size), Ok(id));
+                    }
+                }
+            }
+            let off_face = StickerId {
+                face: RubiksColor::UP,
+                x: size,
+                y: 0,
             };
-            self.faces.insert(RubiksColor::RIGHT, new_face);
+            assert!(off_face.geometry(size).is_err());
+            assert!(StickerId::at_point([0.0; 3], size).is_err());
+            assert!(StickerId::at_point([1.0, 1.0, 0.0], size).is_err());
         }
-        // Step 2: Determine if the back face is being rotated, if so rotate it
-        if *layer == self.face_size - 1 {
-            let face = self.faces.get_mut(&RubiksColor::LEFT).unwrap().to_owned();
-            let new_face = match direction {
-                TurnDirection::CLOCKWISE => rotate_vec_ccw(&face, &self.face_size),
-                TurnDirection::COUNTERCLOCKWISE => rotate_vec_cw(&face, &self.face_size),
+        // Right handed, x right, y up and z out of the front
+        let corner = |face, x, y| StickerId { face, x, y }.geometry(2).unwrap().center;
+        assert_near(corner(RubiksColor::FORWARD, 1, 0), [0.5, 0.5, 1.0]);
+        assert_near(corner(RubiksColor::UP, 1, 1), [0.5, 1.0, 0.5]);
+        assert_near(corner(RubiksColor::RIGHT, 0, 0), [1.0, 0.5, 0.5]);
+    }
+
+    #[test]
+    fn moves_are_rigid_rotations() {
+        for size in SIZES {
+            for face in RubiksColor::ALL {
+                for layer in layers(size) {
+                    for (direction, quarter_turns) in [
+                        (TurnDirection::CLOCKWISE, 1.0),
+                        (TurnDirection::COUNTERCLOCKWISE, -1.0),
+                    ] {
+                        let mut cube = RubiksCube::<StickerId>::solved(size);
+                        cube.apply_move(face, layer, direction);
+                        // Every sticker in the layer turns the same way around the face's axis,
+                        // and nothing else moves
+                        for to_face in RubiksColor::ALL {
+                            for y in 0..size {
+                                for x in 0..size {
+                                    let from = cube.try_get_square(to_face, x, y).unwrap();
+                                    let to = StickerId {
+                                        face: to_face,
+                                        x,
+                                        y,
+                                    };
+                                    let (start, end) =
+                                        (from.geometry(size).unwrap(), to.geometry(size).unwrap());
+                                    let point =
+                                        geometry::sticker_point(from.face, from.x, from.y, size);
+                                    if geometry::in_layer(point, face, layer, size) {
+                                        let turn = |p| geometry::turn_point(p, face, quarter_turns);
+                                        assert_near(turn(start.center), end.center);
+                                        assert_near(turn(start.normal), end.normal);
+                                    } else {
+                                        assert_eq!(from, to);
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn ranks_round_trip() {
+        use coordinates::*;
+        // Every rank unranks to a different arrangement of the right kind, so ranking is a
+        // one to one match between arrangements and numbers
+        for rank in 0..40320 {
+            let permutation = unrank_permutation(rank, 8);
+            let mut sorted = permutation.clone();
+            sorted.sort();
+            assert_eq!(sorted, (0..8).collect::<Vec<_>>());
+            assert_eq!(rank_permutation(&permutation), rank);
+        }
+        for (length, base, count) in [(8, 3, 2187), (12, 2, 2048)] {
+            for rank in 0..count {
+                let orientation = unrank_orientation(rank, length, base);
+                assert_eq!(orientation.len(), length);
+                assert!(orientation.iter().all(|twist| *twist < base));
+                let total: usize = orientation.iter().map(|twist| *twist as usize).sum();
+                assert_eq!(total % base as usize, 0);
+                assert_eq!(rank_orientation(&orientation, base), rank);
+            }
+        }
+        for rank in 0..495 {
+            let picked = unrank_combination(rank, 12, 4);
+            assert_eq!(picked.iter().filter(|p| **p).count(), 4);
+            assert_eq!(rank_combination(&picked), rank);
+        }
+        // The ends of each list are where the comments on the ranking functions say they are
+        assert_eq!(rank_permutation(&[0, 1, 2, 3, 4, 5, 6, 7]), 0);
+        assert_eq!(rank_permutation(&[7, 6, 5, 4, 3, 2, 1, 0]), 40319);
+        let last_four = [[false; 8].as_slice(), &[true; 4]].concat();
+        assert_eq!(rank_combination(&last_four), 0);
+    }
+
+    #[test]
+    fn coordinate_moves_undo() {
+        use coordinates::{Coordinate, CoordinateTable};
+        for coordinate in Coordinate::ALL {
+            // Far too big to build a table for
+            if coordinate == Coordinate::EdgePermutation {
+                continue;
+            }
+            let table = CoordinateTable::new(coordinate).unwrap();
+            for (i, m) in table.moves().iter().enumerate() {
+                let inverse = if m.double { *m } else { m.inverse() };
+                let j = table.moves().iter().position(|m| *m == inverse).unwrap();
+                for value in 0..coordinate.size() {
+                    assert_eq!(
+                        table.apply(table.apply(value, i), j),
+                        value,
+                        "{coordinate:?} {m}"
+                    );
+                }
+            }
+        }
+        // The phase two coordinates lose track of the edges as soon as they leave the middle layer
+        let quarter_turn = "R".parse().unwrap();
+        for coordinate in [Coordinate::UdEdgePermutation, Coordinate::SlicePermutation] {
+            assert!(CoordinateTable::with_moves(coordinate, vec![quarter_turn]).is_err());
+        }
+    }
+
+    #[test]
+    fn pattern_databases_survive_saving() {
+        use coordinates::Coordinate;
+        use pattern_db::{MoveSet, PatternDatabase};
+        let coordinates = [Coordinate::CornerOrientation];
+        let database = PatternDatabase::build(&coordinates, MoveSet::FaceMoves, 2).unwrap();
+        let dir = std::env::temp_dir().join(format!("rubiks_pdb_test_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("twist.pdb");
+        database.save(&path).unwrap();
+        let loaded = PatternDatabase::load(&path).unwrap();
+        assert_eq!(loaded.coordinates(), coordinates);
+        assert_eq!(loaded.move_set(), MoveSet::FaceMoves);
+        for value in 0..Coordinate::CornerOrientation.size() {
+            assert_eq!(loaded.distance(&[value]), database.distance(&[value]));
+        }
+        // A single changed entry or a missing end is noticed when loading
+        let bytes = std::fs::read(&path).unwrap();
+        let damaged = dir.join("damaged.pdb");
+        let mut flipped = bytes.clone();
+        *flipped.last_mut().unwrap() ^= 0x01;
+        std::fs::write(&damaged, &flipped).unwrap();
+        assert!(PatternDatabase::load(&damaged).is_err());
+        std::fs::write(&damaged, &bytes[..bytes.len() - 1]).unwrap();
+        assert!(PatternDatabase::load(&damaged).is_err());
+        drop(loaded);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    // Building the solver's tables takes a while, so they are built once and shared by a solver
+    // that searches on one thread and one that spreads the search over four
+    // The solvers map the saved tables, which stay readable after the files are removed
+    fn test_solvers() -> &'static [solver::Solver; 2] {
+        static SOLVERS: std::sync::OnceLock<[solver::Solver; 2]> = std::sync::OnceLock::new();
+        SOLVERS.get_or_init(|| {
+            let dir =
+                std::env::temp_dir().join(format!("rubiks_solver_test_{}", std::process::id()));
+            solver::build_tables(&dir, parallel::default_threads()).unwrap();
+            let solvers =
+                [1, 4].map(|threads| solver::Solver::load(&dir).unwrap().with_threads(threads));
+            std::fs::remove_dir_all(&dir).unwrap();
+            solvers
+        })
+    }
+
+    fn test_solver() -> &'static solver::Solver {
+        &test_solvers()[1]
+    }
+
+    #[test]
+    fn solving_in_the_phase_two_group_can_leave_it() {
+        // Each of these is already in the phase two group, but is solved quickest by leaving it
+        let options = solver::SolveOptions {
+            max_length: Some(0),
+            time_limit: Some(std::time::Duration::from_secs(10)),
+            ..solver::SolveOptions::default()
+        };
+        for scramble in ["D' L' R U2 L' R", "L2 F B L2 F B", "D F B U2 F' B'"] {
+            let scramble: notation::Algorithm = scramble.parse().unwrap();
+            let cube = RubiksCube::new(3).try_do_algorithm(&scramble).unwrap();
+            let solution = test_solver().solve_with(&cube, &options, &|_| {}).unwrap();
+            assert!(cube.try_do_algorithm(&solution).unwrap().is_solved());
+            assert!(solution.moves.len() <= scramble.moves.len(), "{solution}");
+        }
+    }
+
+    #[test]
+    fn threads_find_the_same_solution() {
+        let [one, four] = test_solvers();
+        // Each with a length that can be reached quickly, so the search ends on a solution
+        for (scramble, max_length) in [
+            ("R U2 F' D L2 B U' R2 F D2", 18),
+            ("F2 L' D B2 U R' F L2 D' B U2 R", 20),
+            ("U R2 F B R B2 R U2 L B2 R U' D' R2 F R' L B2 U2 F2", 22),
+        ] {
+            let cube = RubiksCube::new(3)
+                .try_do_algorithm(&scramble.parse().unwrap())
+                .unwrap();
+            assert_eq!(one.solve(&cube), four.solve(&cube), "{scramble}");
+            let options = solver::SolveOptions {
+                max_length: Some(max_length),
+                ..solver::SolveOptions::default()
             };
-            self.faces.insert(RubiksColor::LEFT, new_face);
-        }
-        // Step 3: Determine the strips allong the top, bottom, left, and right that are going to
-        // be swapped
-        // Step 3.1: Top, Bottom, and Front faces all itterate the same way here
-        let mut top_strip: Vec<RubiksColor> = vec![];
-        let mut top_face = self.faces.get_mut(&RubiksColor::UP).unwrap().to_owned();
-        let mut bottom_strip: Vec<RubiksColor> = vec![];
-        let mut bottom_face = self.faces.get_mut(&RubiksColor::DOWN).unwrap().to_owned();
-        let mut front_strip: Vec<RubiksColor> = vec![];
-        let mut front_face = self
-            .faces
-            .get_mut(&RubiksColor::FORWARD)
-            .unwrap()
-            .to_owned();
-
-        for y in (0..self.face_size).rev() {
-            top_strip.push(
-                read_2d_vec(
-                    &top_face,
-                    &self.face_size,
-                    &(self.face_size - 1 - layer),
-                    &y,
-                )
-                .unwrap()
-                .clone(),
-            );
-            bottom_strip.push(
-                read_2d_vec(
-                    &bottom_face,
-                    &self.face_size,
-                    &(self.face_size - 1 - layer),
-                    &y,
-                )
-                .unwrap()
-                .clone(),
-            );
-            bottom_strip.push(
-                read_2d_vec(
-                    &top_face,
-                    &self.face_size,
-                    &(self.face_size - 1 - layer),
-                    &y,
-                )
-                .unwrap()
-                .clone(),
+            let solution = one.solve_with(&cube, &options, &|_| {}).unwrap();
+            assert!(solution.moves.len() <= max_length, "{solution}");
+            assert_eq!(
+                four.solve_with(&cube, &options, &|_| {}),
+                Ok(solution),
+                "{scramble}"
             );
         }
+    }
 
-        let mut back_strip: Vec<RubiksColor> = vec![];
-        let mut back_face = self.faces.get_mut(&RubiksColor::BACK).unwrap().to_owned();
+    #[test]
+    fn cancelled_solves_stop_straight_away() {
+        let cube = RubiksCube::new(3)
+            .try_do_algorithm(
+                &"U R2 F B R B2 R U2 L B2 R U' D' R2 F R' L B2 U2 F2"
+                    .parse()
+                    .unwrap(),
+            )
+            .unwrap();
+        let cancel = solver::CancelToken::new();
+        cancel.cancel();
+        let options = solver::SolveOptions {
+            max_length: Some(0),
+            cancel: Some(cancel),
+            ..solver::SolveOptions::default()
+        };
+        let solver = test_solver();
+        let start = std::time::Instant::now();
+        let result = solver.solve_with(&cube, &options, &|_| {});
+        assert!(matches!(result, Err(CubeError::NotFound(_))), "{result:?}");
+        assert!(start.elapsed() < std::time::Duration::from_secs(2));
+    }
 
-        for y in 0..self.face_size {
-            back_strip.push(
-                read_2d_vec(
-                    &back_face,
-                    &self.face_size,
-                    &(self.face_size - 1 - layer),
-                    &y,
-                )
-                .unwrap()
-                .clone(),
-            );
+    #[test]
+    fn node_limits_keep_the_best_solution() {
+        let cube = RubiksCube::new(3)
+            .try_do_algorithm(
+                &"U R2 F B R B2 R U2 L B2 R U' D' R2 F R' L B2 U2 F2"
+                    .parse()
+                    .unwrap(),
+            )
+            .unwrap();
+        // The first solution turns up after about 200,000 positions, and without the limit this
+        // would keep looking for shorter ones for a long time
+        let limit = 500_000;
+        let options = solver::SolveOptions {
+            max_length: Some(0),
+            node_limit: Some(limit),
+            ..solver::SolveOptions::default()
+        };
+        let reports = std::sync::Mutex::new(vec![]);
+        let solution = test_solver()
+            .solve_with(&cube, &options, &|progress| {
+                reports.lock().unwrap().push(progress.clone())
+            })
+            .unwrap();
+        assert!(cube.try_do_algorithm(&solution).unwrap().is_solved());
+        let last = reports.into_inner().unwrap().pop().unwrap();
+        assert_eq!(last.best, Some(solution));
+        assert!(
+            last.nodes >= limit && last.nodes < 2 * limit,
+            "{}",
+            last.nodes
+        );
+    }
+
+    #[test]
+    fn nets_show_where_stickers_went() {
+        let cube = RubiksCube::new(3).do_move(RubiksColor::RIGHT, 0, TurnDirection::CLOCKWISE);
+        // The front's right column goes up, the top's goes round to the back upside down, and so on
+        let expected = [
+            "    UUF",
+            "    UUF",
+            "    UUF",
+            "LLL FFD RRR UBB",
+            "LLL FFD RRR UBB",
+            "LLL FFD RRR UBB",
+            "    DDB",
+            "    DDB",
+            "    DDB",
+        ];
+        assert_eq!(cube.to_string(), expected.join("\n"));
+        let small = RubiksCube::new(2).do_move(RubiksColor::UP, 0, TurnDirection::CLOCKWISE);
+        assert_eq!(
+            small.net(net::NetLetters::COLORS).to_string(),
+            "   WW\n   WW\nGG RR BB OO\nOO GG RR BB\n   YY\n   YY"
+        );
+        assert_eq!(RubiksCube::new(1).to_string(), "  U\nL F R B\n  D");
+    }
+
+    #[test]
+    fn colored_nets_fit_and_use_the_palette() {
+        use net::{palette_index, ColorMode, ColorScheme};
+        // The six standard colors
+        let expected = [231, 220, 202, 125, 29, 25];
+        for (face, index) in RubiksColor::ALL.into_iter().zip(expected) {
+            assert_eq!(palette_index(ColorScheme::STANDARD.rgb(face)), index);
         }
+        // Corners of the color cube, and grays from the ends and middle of the gray ramp
+        assert_eq!(palette_index([0, 0, 0]), 16);
+        assert_eq!(palette_index([0, 0, 255]), 21);
+        assert_eq!(palette_index([8, 8, 8]), 232);
+        assert_eq!(palette_index([128, 128, 128]), 244);
+        assert_eq!(palette_index([238, 238, 238]), 255);
+        // Stickers are two columns wide until the net doesn't fit, and never less than one
+        let cube = RubiksCube::new(2).do_move(RubiksColor::UP, 0, TurnDirection::CLOCKWISE);
+        let net = |mode, columns| {
+            cube.color_net(ColorScheme::STANDARD, mode)
+                .fit_width(columns)
+                .to_string()
+        };
+        assert_eq!(
+            net(ColorMode::Plain, 80),
+            "     W W \n     W W \nG G  R R  B B  O O \nO O  G G  R R  B B \n     Y Y \n     Y Y "
+        );
+        assert_eq!(
+            net(ColorMode::Plain, 18),
+            "   WW\n   WW\nGG RR BB OO\nOO GG RR BB\n   YY\n   YY"
+        );
+        assert_eq!(net(ColorMode::Plain, 0), net(ColorMode::Plain, 18));
+        assert_eq!(
+            net(ColorMode::TrueColor, 19).lines().nth(2).unwrap(),
+            "\x1b[48;2;0;155;72m  \x1b[0m\x1b[48;2;0;155;72m  \x1b[0m \
+             \x1b[48;2;183;18;52m  \x1b[0m\x1b[48;2;183;18;52m  \x1b[0m \
+             \x1b[48;2;0;70;173m  \x1b[0m\x1b[48;2;0;70;173m  \x1b[0m \
+             \x1b[48;2;255;88;0m  \x1b[0m\x1b[48;2;255;88;0m  \x1b[0m"
+        );
+        assert_eq!(
+            net(ColorMode::Palette256, 18).lines().next().unwrap(),
+            "   \x1b[48;5;231m \x1b[0m\x1b[48;5;231m \x1b[0m"
+        );
+    }
 
-        // Step 4: Swap the faces forwards if clockwise, backwards if counterclockwise
-        let mut new_top: Vec<RubiksColor>;
-        let mut new_back: Vec<RubiksColor>;
-        let mut new_bottom: Vec<RubiksColor>;
-        let mut new_front: Vec<RubiksColor>;
+    #[test]
+    fn svgs_draw_every_sticker() {
+        use svg::{SvgOptions, SvgView};
+        let count = |svg: &str, pattern: &str| svg.matches(pattern).count();
+        let fill = |rgb| format!(r#"fill="{}""#, svg::hex(rgb));
+        let color = |face| fill(net::ColorScheme::STANDARD.rgb(face));
+        let body = fill(SvgOptions::default().body);
+        // Each face drawn is its body with a sticker on top for every sticker it shows
+        let cube = RubiksCube::new(3);
+        for (view, faces, stickers) in [
+            (SvgView::Net, 6, 54),
+            (SvgView::Isometric, 3, 27),
+            (SvgView::LastLayer, 5, 21),
+        ] {
+            let svg = cube.to_svg(&SvgOptions {
+                view,
+                ..Default::default()
+            });
+            assert!(svg.starts_with("<svg ") && svg.ends_with("</svg>\n"));
+            assert_eq!(count(&svg, "<polygon"), faces + stickers, "{view:?}");
+            assert_eq!(count(&svg, &body), faces, "{view:?}");
+        }
+        // Four faces across and three down, with a quarter sticker between faces and around them
+        let svg = cube.to_svg(&SvgOptions::default());
+        assert!(svg.starts_with(
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="265" height="200" viewBox="-5 -5 265 200">"#
+        ));
+        // After R the view from the front shows green on top and yellow on the front's right
+        let turned = cube
+            .clone()
+            .do_move(RubiksColor::RIGHT, 0, TurnDirection::CLOCKWISE);
+        let svg = turned.to_svg(&SvgOptions {
+            view: SvgView::Isometric,
+            ..Default::default()
+        });
+        for (face, stickers) in [
+            (RubiksColor::UP, 6),
+            (RubiksColor::FORWARD, 9),
+            (RubiksColor::DOWN, 3),
+            (RubiksColor::RIGHT, 9),
+        ] {
+            assert_eq!(count(&svg, &color(face)), stickers, "{face:?}");
+        }
+        // Masked stickers lose their color, highlighted ones get an outline and arrows add a shaft
+        // and a head
+        let up = |x, y| StickerId {
+            face: RubiksColor::UP,
+            x,
+            y,
+        };
+        let options = SvgOptions {
+            mask: vec![up(0, 0), up(1, 0)],
+            highlight: vec![up(2, 2)],
+            arrows: vec![(up(0, 0), up(2, 2))],
+            ..Default::default()
+        };
+        let svg = cube.to_svg(&options);
+        assert_eq!(count(&svg, &fill(options.mask_color)), 2);
+        assert_eq!(count(&svg, &color(RubiksColor::UP)), 7);
+        let stroke = format!(r#"stroke="{}""#, svg::hex(options.highlight_color));
+        assert_eq!(count(&svg, &stroke), 1);
+        assert_eq!(count(&svg, &fill(options.arrow_color)), 2);
+        assert_eq!(count(&svg, "<polygon"), 62);
+        assert_eq!(svg::number(2.5), "2.5");
+        assert_eq!(svg::number(3.0), "3");
+        assert_eq!(svg::number(-0.001), "0");
+    }
 
-        match direction {
-            TurnDirection::CLOCKWISE => {
-                new_top = front_strip;
-                new_back = top_strip;
-                new_bottom = back_strip;
-                new_front = bottom_strip;
-            }
-            TurnDirection::COUNTERCLOCKWISE => {
-                new_top = back_strip;
-                new_back = bottom_strip;
-                new_bottom = front_strip;
-                new_front = bottom_strip;
+    #[test]
+    fn diagrams_mask_and_point_out_the_case() {
+        use diagram::{Diagram, Stage};
+        let count = |svg: &str, rgb| svg.matches(&format!(r#"fill="{}""#, svg::hex(rgb))).count();
+        let options = svg::SvgOptions::default();
+        let white = net::ColorScheme::STANDARD.rgb(RubiksColor::UP);
+        let up = |x, y| StickerId {
+            face: RubiksColor::UP,
+            x,
+            y,
+        };
+        // The T perm swaps the up right and up back right pieces with their neighbours, so every
+        // one of its four up stickers gets an arrow to where it goes
+        let t_perm = "R U R' U' R' F R2 U' R' U' R U R' F'".parse().unwrap();
+        let pll = Diagram {
+            stage: Stage::Ll,
+            arrows: true,
+            ..Default::default()
+        };
+        let arrows: HashSet<_> = pll
+            .last_layer_arrows(&t_perm)
+            .unwrap()
+            .into_iter()
+            .collect();
+        let expected = [
+            (up(2, 0), up(2, 2)),
+            (up(2, 2), up(2, 0)),
+            (up(0, 1), up(2, 1)),
+            (up(2, 1), up(0, 1)),
+        ];
+        assert_eq!(arrows, expected.into_iter().collect());
+        let svg = pll.last_layer(&t_perm).unwrap();
+        assert_eq!(count(&svg, options.arrow_color), 2 * expected.len());
+        assert_eq!(count(&svg, options.mask_color), 0);
+        // OLL only shows the up colored stickers, wherever they are in the last layer, and the
+        // Sune leaves three of them on the sides
+        let sune = "R U R' U R U2 R'".parse().unwrap();
+        let oll = Diagram {
+            stage: Stage::Oll,
+            ..Default::default()
+        };
+        let svg = oll.last_layer(&sune).unwrap();
+        assert_eq!(count(&svg, white), 9);
+        assert_eq!(count(&svg, options.mask_color), 12);
+        let solved = oll.last_layer(&notation::Algorithm { moves: vec![] });
+        assert_eq!(count(&solved.unwrap(), options.mask_color), 12);
+        assert!(!pll.last_layer_arrows(&sune).unwrap().is_empty());
+        // F2L greys out the up face and the top row of the front and right
+        let f2l = Diagram {
+            stage: Stage::F2l,
+            ..Default::default()
+        };
+        let svg = f2l.f2l(&"R U R'".parse().unwrap()).unwrap();
+        assert_eq!(count(&svg, options.mask_color), 15);
+    }
+
+    #[test]
+    fn images_are_drawn_and_written() {
+        use render::{crc32, Camera, Image, RenderOptions};
+        // Straight in front with the light behind the camera, the front face is its own color
+        let options = RenderOptions {
+            width: 64,
+            height: 48,
+            camera: Camera::orbit(0.0, 0.0, 6.5),
+            light: [0.0, 0.0, 1.0],
+            ..Default::default()
+        };
+        let image = RubiksCube::new(3)
+            .try_do_algorithm(&"R".parse().unwrap())
+            .unwrap()
+            .render(&options);
+        assert_eq!((image.width(), image.height()), (64, 48));
+        assert_eq!(image.pixel(0, 0), Some(options.background));
+        let rgb = |color| options.scheme.rgb(color);
+        assert_eq!(image.pixel(32, 24), Some(rgb(RubiksColor::FORWARD)));
+        assert_eq!(image.pixel(23, 24), Some(rgb(RubiksColor::FORWARD)));
+        assert_eq!(image.pixel(41, 24), Some(rgb(RubiksColor::DOWN)));
+        assert_eq!(image.pixel(64, 0), None);
+
+        // A PPM is its header and then every pixel as it is
+        let mut ppm = vec![];
+        image.write_ppm(&mut ppm).unwrap();
+        let header = b"P6\n64 48\n255\n";
+        assert_eq!(&ppm[..header.len()], header);
+        assert_eq!(ppm.len(), header.len() + 64 * 48 * 3);
+        assert_eq!(
+            ppm[header.len() + (24 * 64 + 41) * 3..][..3],
+            rgb(RubiksColor::DOWN)
+        );
+
+        // Big enough for the pixels to need more than one stored block
+        assert_eq!(crc32(b"123456789".iter()), 0xCBF4_3926);
+        let mut image = Image::new(200, 150, [1, 2, 3]);
+        image.set_pixel(199, 149, [250, 0, 7]);
+        let mut png = vec![];
+        image.write_png(&mut png).unwrap();
+        assert_eq!(&png[..8], b"\x89PNG\r\n\x1a\n");
+        let mut chunks = vec![];
+        let mut rest = &png[8..];
+        while !rest.is_empty() {
+            let length = u32::from_be_bytes(rest[..4].try_into().unwrap()) as usize;
+            let (body, crc) = rest[4..].split_at(4 + length);
+            assert_eq!(crc32(body.iter()).to_be_bytes(), crc[..4]);
+            chunks.push((&body[..4], &body[4..]));
+            rest = &crc[4..];
+        }
+        let kinds: Vec<_> = chunks.iter().map(|(kind, _)| *kind).collect();
+        assert_eq!(kinds, [b"IHDR", b"IDAT", b"IEND"]);
+        assert_eq!(chunks[0].1, [0, 0, 0, 200, 0, 0, 0, 150, 8, 2, 0, 0, 0]);
+        // Undo the stored blocks and check every row is unfiltered and holds the pixels
+        let mut data = &chunks[1].1[2..];
+        let mut raw = vec![];
+        loop {
+            let length = u16::from_le_bytes([data[1], data[2]]) as usize;
+            raw.extend(&data[5..5 + length]);
+            let last = data[0] == 1;
+            data = &data[5 + length..];
+            if last {
+                break;
+            }
+        }
+        assert_eq!(data.len(), 4);
+        assert_eq!(raw.len(), 150 * (1 + 200 * 3));
+        for (y, row) in raw.chunks(1 + 200 * 3).enumerate() {
+            assert_eq!(row[0], 0);
+            for (x, pixel) in row[1..].chunks(3).enumerate() {
+                assert_eq!(Some(pixel), image.pixel(x, y).as_ref().map(|p| &p[..]));
             }
+        }
+    }
+
+    #[test]
+    fn animations_turn_each_move_a_frame_at_a_time() {
+        let animation = animation::Animation {
+            frames_per_move: 4,
+            ..Default::default()
         };
-        // Step 5: Write new strips to new faces
-        new_top.reverse();
-        new_back.reverse();
-        new_bottom.reverse();
-        new_front.reverse();
-        for y in (0..self.face_size).rev() {
-            write_2d_vec(
-                &mut top_face,
-                &self.face_size,
-                &(self.face_size - 1 - layer),
-                &y,
-                new_top.pop().unwrap(),
-            );
-            write_2d_vec(
-                &mut bottom_face,
-                &self.face_size,
-                &(self.face_size - 1 - layer),
-                &y,
-                new_bottom.pop().unwrap(),
-            );
-            write_2d_vec(
-                &mut front_face,
-                &self.face_size,
-                &(self.face_size - 1 - layer),
-                &y,
-                new_front.pop().unwrap(),
-            );
+        let cube = RubiksCube::new(3);
+        let algorithm: notation::Algorithm = "R U2 R'".parse().unwrap();
+        let frames = animation.frames(&cube, &algorithm).unwrap();
+        assert_eq!(frames.len(), 3 * 4 + 1);
+        // Every move starts from where the last one left the cube, not turned at all yet
+        let after_r = cube.try_do_algorithm(&"R".parse().unwrap()).unwrap();
+        for (frame, cube, label) in [(0, &cube, "R"), (4, &after_r, "U2")] {
+            assert_eq!(&frames[frame].cube, cube);
+            assert_eq!(frames[frame].label, label);
+            assert_eq!(frames[frame].turn.unwrap().quarter_turns, 0.0);
         }
+        // Half way through, a half turn has gone a quarter and a counterclockwise turn backwards
+        let halfway = |frame: usize| frames[frame].turn.unwrap().quarter_turns;
+        assert_eq!(frames[2].turn.unwrap().face, RubiksColor::RIGHT);
+        assert_eq!(halfway(2), 0.5);
+        assert_eq!(halfway(6), 1.0);
+        assert_eq!(halfway(10), -0.5);
+        assert!(halfway(1) > 0.0 && halfway(1) < 0.25);
+        let last = frames.last().unwrap();
+        assert_eq!(last.cube, cube.try_do_algorithm(&algorithm).unwrap());
+        assert!(last.turn.is_none());
+        assert_eq!(last.label, "");
 
-        for y in 0..self.face_size {
-            write_2d_vec(
-                &mut back_face,
-                &self.face_size,
-                &(self.face_size - 1 - layer),
-                &y,
-                new_back.pop().unwrap(),
-            );
+        let nothing = notation::Algorithm { moves: vec![] };
+        assert_eq!(animation.frames(&cube, &nothing).unwrap().len(), 1);
+        assert!(animation
+            .frames(&RubiksCube::new(2), &"3R".parse().unwrap())
+            .is_err());
+    }
+
+    #[test]
+    fn meshes_are_made_of_the_outside_pieces() {
+        let options = mesh::MeshOptions::default();
+        let obj = |cube: &RubiksCube| {
+            let mut obj = vec![];
+            cube.mesh(&options).write_obj(&mut obj, "cube.mtl").unwrap();
+            String::from_utf8(obj).unwrap()
+        };
+        let lines = |obj: &str, start| obj.lines().filter(|l| l.starts_with(start)).count();
+        // Every piece but the hidden middle, and a box side for each plus one quad per sticker
+        for (size, pieces) in [(1, 1), (2, 8), (3, 26), (4, 56)] {
+            let obj = obj(&RubiksCube::new(size));
+            assert_eq!(lines(&obj, "o "), pieces);
+            assert_eq!(lines(&obj, "f "), 6 * pieces + 6 * size * size);
         }
-        // Step 6: Place new faces back onto cube
-        self.faces.insert(RubiksColor::UP, top_face);
-        self.faces.insert(RubiksColor::FORWARD, front_face);
-        self.faces.insert(RubiksColor::DOWN, bottom_face);
-        self.faces.insert(RubiksColor::BACK, back_face);
+        let obj = obj(&RubiksCube::new(3));
+        assert!(!obj.contains("o piece_1_1_1\n"));
+        let corner = obj.split("o ").find(|o| o.starts_with("piece_0_0_0\n"));
+        assert_eq!(lines(corner.unwrap(), "usemtl sticker_"), 3);
 
-        self
+        // The same pieces in glTF, a node and a mesh for each under one root node, and a
+        // primitive for the body and each sticker color on them
+        let gltf = RubiksCube::new(3).mesh(&options).to_gltf();
+        assert_eq!(gltf.matches(r#"{"name":"piece_"#).count(), 2 * 26);
+        assert_eq!(gltf.matches(r#""mesh":"#).count(), 26);
+        let children: Vec<String> = (0..26).map(|i| i.to_string()).collect();
+        let root = format!(r#"{{"name":"cube","children":[{}]}}"#, children.join(","));
+        assert!(gltf.contains(&root));
+        assert!(gltf.contains(r#""scenes":[{"nodes":[26]}]"#));
+        assert_eq!(
+            gltf.matches(r#""material":"#).count(),
+            8 * 4 + 12 * 3 + 6 * 2
+        );
+        // 210 quads of four vertices, each a position and a normal, and six indices
+        let quads = 26 * 6 + 54;
+        let length = 2 * quads * 4 * 3 * 4 + quads * 6 * 4;
+        assert!(gltf.contains(&format!(r#""buffers":[{{"byteLength":{length},"#)));
+    }
+
+    #[test]
+    fn virtual_cube_keys_turn_and_time_the_cube() {
+        use tui::{Session, Timer};
+        let after = |moves: &str| {
+            RubiksCube::new(3)
+                .try_do_algorithm(&moves.parse().unwrap())
+                .unwrap()
+        };
+        let mut session = Session::new(3).unwrap();
+        // Keys are the same with shift held, and whole cube rotations aren't counted as moves
+        assert!(session.press(b'i').unwrap());
+        assert!(session.press(b'J').unwrap());
+        assert_eq!(session.cube, after("R U"));
+        session.press(b't').unwrap();
+        assert_eq!(session.cube, after("R U R 2R 3R"));
+        session.press(b'5').unwrap();
+        assert_eq!(session.cube, after("R U R 2R 3R 2L"));
+        assert_eq!(session.moves, 3);
+        assert_eq!(session.timer, Timer::Idle);
+        session.press(b'?').unwrap();
+        assert_eq!(session.moves, 3);
+
+        // The timer starts with the first move after a scramble, not a rotation, and stops once
+        // every face is one color
+        session.press(b' ').unwrap();
+        assert_eq!((session.timer, session.moves), (Timer::Ready, 0));
+        session.cube = after("R U");
+        session.press(b'y').unwrap();
+        assert_eq!(session.timer, Timer::Ready);
+        session.press(b'b').unwrap();
+        session.press(b'f').unwrap();
+        assert!(matches!(session.timer, Timer::Running(_)));
+        session.press(b'k').unwrap();
+        assert!(matches!(session.timer, Timer::Finished(_)));
+        assert_eq!(session.moves, 2);
+        session.press(b'\x1b').unwrap();
+        assert_eq!(session.cube, RubiksCube::new(3));
+        assert_eq!((session.timer, session.moves), (Timer::Idle, 0));
+
+        // A 2x2 has no slices to turn
+        let mut session = Session::new(2).unwrap();
+        for key in *b"5x28" {
+            session.press(key).unwrap();
+        }
+        assert_eq!(session.cube, RubiksCube::new(2));
+        assert_eq!(session.moves, 0);
+        assert!(!session.press(0x03).unwrap());
     }
 
-    fn get_square(self, face: RubiksColor, x: u8, y: u8) -> Option<RubiksColor> {
-        let t_face: &Vec<RubiksColor> = self.faces.get(&face)?;
-        let val: Option<&RubiksColor> = t_face.get(usize::from(x + y * self.face_size));
-        match val {
-            Some(v) => Some(v.to_owned()),
-            None => None,
+    // Plays a script through the repl, returning everything it wrote
+    fn run_repl(face_size: usize, script: &str) -> String {
+        let mut out = vec![];
+        repl::Repl::new(face_size)
+            .unwrap()
+            .with_solver(test_solver().clone())
+            .run(script.as_bytes(), &mut out)
+            .unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn repl_turns_and_undoes() {
+        let facelets = |moves: &str| {
+            RubiksCube::new(3)
+                .try_do_algorithm(&moves.parse().unwrap())
+                .unwrap()
+                .to_facelets()
+        };
+        let out = run_repl(
+            3,
+            "R U 2F'\nshow facelets\nundo\nshow facelets\nhistory\nredo\nshow facelets\n\
+             undo 10\nshow facelets\nredo 2\nL\nredo\nhistory\nreset\nshow facelets\n",
+        );
+        let lines: Vec<&str> = out
+            .lines()
+            .map(|line| line.trim_start_matches("> "))
+            .collect();
+        let position = |line: &str| lines.iter().position(|l| *l == line).unwrap();
+        assert!(position(&facelets("R U 2F'")) < position(&facelets("R U")));
+        assert!(lines.contains(&"moves (2): R U"));
+        assert_eq!(
+            lines.iter().filter(|l| **l == facelets("R U 2F'")).count(),
+            2
+        );
+        // Undoing more than there is stops at the scramble, and a new move forgets what was undone
+        assert_eq!(lines.iter().filter(|l| **l == facelets("")).count(), 2);
+        assert!(lines.contains(&"moves (3): R U L"));
+    }
+
+    #[test]
+    fn repl_reports_mistakes_and_carries_on() {
+        let out = run_repl(
+            3,
+            "X\nR3\n4R\nundo many\nsize\nsize 0\nsize 100000\nsize 2\nsolve\nR\nhistory\nquit\nU\n",
+        );
+        for error in [
+            "error: couldn't parse \"X\" as a move",
+            "error: couldn't parse \"R3\" as a move",
+            "error: layer 3 does not exist on a cube with 3 layers",
+            "error: couldn't parse \"many\" as a number of moves",
+            "error: couldn't parse \"size\" as a move",
+            "error: a cube can't have a face size of 0",
+            "error: a 100000x100000 cube would be too large",
+            "error: expected a 3x3 cube, not a 2x2",
+        ] {
+            assert!(out.contains(error), "{error}");
         }
+        // Nothing that failed was kept, and nothing after quit was run
+        assert!(out.contains("moves (1): R"));
+        assert!(!out.contains("moves (2)"));
+        assert!(!out.contains("\"U\""));
     }
-}
 
-fn main() {
-    // Create a 3x3x3 cube, and turn the center layer facing the player upwards (ie, the second
-    // layer when viewed from the right)
-    let cube = RubiksCube::new(3).do_move(RubiksColor::RIGHT, 1, TurnDirection::CLOCKWISE);
+    #[test]
+    fn repl_scrambles_and_solves() {
+        let out = run_repl(3, "scramble\nsolve\nshow facelets\nsolve\nhistory\n");
+        assert!(out.contains(&RubiksCube::new(3).to_facelets()));
+        assert!(out.contains("already solved"));
+        let history = out.lines().find(|l| l.contains("scramble: ")).unwrap();
+        assert!(!history.ends_with("none"));
+    }
 }
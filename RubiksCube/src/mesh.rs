@@ -0,0 +1,427 @@
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use crate::geometry::{face_normal, face_point, sticker_point};
+use crate::net::ColorScheme;
+use crate::{read_2d_vec, CubeError, RubiksColor, RubiksCube};
+
+// Exporting a cube as a 3D model for other programs, as Wavefront OBJ with its MTL materials or as
+// glTF 2.0
+// The model is made of the pieces a real cube has, each a plain box with its stickers on top, and
+// every piece is its own object so layer turns can be animated afterwards. Pieces all keep the
+// middle of the cube as their origin, so turning a layer is the same rotation keyed on each of its
+// pieces. Positions are in the cube units of the geometry module, reaching from -1 to 1
+
+// How far stickers stand out from the piece they are on, so they don't flicker against it
+const STICKER_LIFT: f64 = 0.002;
+
+// The glTF numbers for the kinds of data stored in the buffer
+const GLTF_FLOAT: u32 = 5126;
+const GLTF_UNSIGNED_INT: u32 = 5125;
+const GLTF_ARRAY_BUFFER: u32 = 34962;
+const GLTF_ELEMENT_ARRAY_BUFFER: u32 = 34963;
+
+#[derive(Clone, Debug)]
+pub(crate) struct MeshOptions {
+    pub(crate) scheme: ColorScheme,
+    pub(crate) body: [u8; 3], // The color of the pieces under the stickers
+    pub(crate) gap: f64,      // How much of each sticker's square is left as body around it
+}
+
+impl Default for MeshOptions {
+    fn default() -> Self {
+        MeshOptions {
+            scheme: ColorScheme::default(),
+            body: [24, 24, 24],
+            gap: 0.1,
+        }
+    }
+}
+
+// What a quad is colored with
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
+enum Material {
+    Body,
+    Sticker(RubiksColor),
+}
+
+impl Material {
+    // The body first, then the sticker colors in the order of RubiksColor::ALL
+    const ALL: [Material; 7] = [
+        Material::Body,
+        Material::Sticker(RubiksColor::UP),
+        Material::Sticker(RubiksColor::DOWN),
+        Material::Sticker(RubiksColor::LEFT),
+        Material::Sticker(RubiksColor::RIGHT),
+        Material::Sticker(RubiksColor::FORWARD),
+        Material::Sticker(RubiksColor::BACK),
+    ];
+
+    fn name(&self) -> String {
+        match self {
+            Material::Body => "body".to_string(),
+            Material::Sticker(color) => format!("sticker_{}", color.letter()),
+        }
+    }
+}
+
+// A flat quad, with its corners going counterclockwise as it is looked at from the front
+struct Quad {
+    corners: [[f64; 3]; 4],
+    normal: [f64; 3],
+    material: Material,
+}
+
+// One piece of the cube
+// Its position counts pieces from the left, bottom and back, so it is the same on every axis as
+// the layer it is in counted from the left, down and back faces
+pub(crate) struct Piece {
+    position: [usize; 3],
+    quads: Vec<Quad>,
+}
+
+impl Piece {
+    pub(crate) fn name(&self) -> String {
+        let [x, y, z] = self.position;
+        format!("piece_{x}_{y}_{z}")
+    }
+}
+
+// A cube as pieces ready to be written out
+pub(crate) struct Mesh {
+    pieces: Vec<Piece>,
+    colors: [[u8; 3]; 7], // For each material in the order of Material::ALL
+}
+
+impl RubiksCube {
+    pub(crate) fn mesh(&self, options: &MeshOptions) -> Mesh {
+        let n = self.face_size;
+        // Step 1: A box for every piece on the outside of the cube, none for the hidden middle
+        let last = n - 1;
+        let mut pieces = vec![];
+        let mut piece_at = HashMap::new();
+        for x in 0..n {
+            for y in 0..n {
+                for z in 0..n {
+                    let position = [x, y, z];
+                    if position.iter().all(|c| (1..last).contains(c)) {
+                        continue;
+                    }
+                    piece_at.insert(position, pieces.len());
+                    pieces.push(Piece {
+                        position,
+                        quads: box_quads(position, n),
+                    });
+                }
+            }
+        }
+        // Step 2: Put each sticker on the piece it is part of, which is the one whose box the
+        // middle of the sticker is on
+        for face in RubiksColor::ALL {
+            let normal = face_normal(face);
+            for y in 0..n {
+                for x in 0..n {
+                    let Some(color) = read_2d_vec(self.face(face), &n, &x, &y) else {
+                        continue;
+                    };
+                    let center = sticker_point(face, x, y, n);
+                    let position =
+                        center.map(|c| ((c + n as i64 - 1).clamp(0, 2 * last as i64) / 2) as usize);
+                    let (a, b) = (2 * x as i64 - (n as i64 - 1), (n as i64 - 1) - 2 * y as i64);
+                    // Around the sticker counterclockwise, pulled in towards its middle to leave
+                    // the gap and lifted off the box
+                    let corners = [(-1, -1), (1, -1), (1, 1), (-1, 1)].map(|(da, db)| {
+                        let corner = face_point(face, a, b, n);
+                        let offset = face_point(face, a + da, b + db, n);
+                        [0, 1, 2].map(|i| {
+                            let inset = corner[i] as f64
+                                + (offset[i] - corner[i]) as f64 * (1.0 - options.gap);
+                            inset / n as f64 + normal[i] * STICKER_LIFT
+                        })
+                    });
+                    pieces[piece_at[&position]].quads.push(Quad {
+                        corners,
+                        normal,
+                        material: Material::Sticker(*color),
+                    });
+                }
+            }
+        }
+        let mut colors = [options.body; 7];
+        for (i, color) in RubiksColor::ALL.iter().enumerate() {
+            colors[i + 1] = options.scheme.rgb(*color);
+        }
+        Mesh { pieces, colors }
+    }
+}
+
+// The six sides of the box for the piece at position, each facing out
+fn box_quads(position: [usize; 3], face_size: usize) -> Vec<Quad> {
+    let n = face_size as f64;
+    // Where the box starts and ends along each axis
+    let low = position.map(|c| -1.0 + 2.0 * c as f64 / n);
+    let high = position.map(|c| -1.0 + 2.0 * (c + 1) as f64 / n);
+    RubiksColor::ALL
+        .iter()
+        .map(|face| {
+            let normal = face_normal(*face);
+            // The side's own axis is fixed at the end it faces, and the other two go around it
+            let axis = (0..3).find(|i| normal[*i] != 0.0).unwrap();
+            let (u, v) = ((axis + 1) % 3, (axis + 2) % 3);
+            let mut corners =
+                [(false, false), (true, false), (true, true), (false, true)].map(|(at_u, at_v)| {
+                    let mut corner = [0.0; 3];
+                    corner[axis] = if normal[axis] > 0.0 {
+                        high[axis]
+                    } else {
+                        low[axis]
+                    };
+                    corner[u] = if at_u { high[u] } else { low[u] };
+                    corner[v] = if at_v { high[v] } else { low[v] };
+                    corner
+                });
+            // Going from u to v is counterclockwise seen from the positive end of the axis
+            if normal[axis] < 0.0 {
+                corners.reverse();
+            }
+            Quad {
+                corners,
+                normal,
+                material: Material::Body,
+            }
+        })
+        .collect()
+}
+
+impl Mesh {
+    // Saves as OBJ, with the materials next to it in a file of the same name ending in .mtl, or
+    // as glTF, going by the file's extension
+    pub(crate) fn save(&self, path: impl AsRef<Path>) -> Result<(), CubeError> {
+        let path = path.as_ref();
+        let extension = path.extension().and_then(|e| e.to_str()).unwrap_or("");
+        match extension.to_ascii_lowercase().as_str() {
+            "obj" => {
+                let mtl_path = path.with_extension("mtl");
+                let mtl_name = mtl_path
+                    .file_name()
+                    .and_then(|name| name.to_str())
+                    .unwrap_or("cube.mtl");
+                let mut out = BufWriter::new(File::create(path)?);
+                self.write_obj(&mut out, mtl_name)?;
+                out.flush()?;
+                let mut out = BufWriter::new(File::create(&mtl_path)?);
+                self.write_mtl(&mut out)?;
+                out.flush()?;
+            }
+            "gltf" => std::fs::write(path, self.to_gltf())?,
+            _ => {
+                return Err(CubeError::Parse(format!(
+                    "\"{}\" as a model file name, it should end in .obj or .gltf",
+                    path.display()
+                )))
+            }
+        }
+        Ok(())
+    }
+
+    // Every piece as its own object, with its quads grouped by material
+    // Vertices aren't shared between quads, so each quad keeps its own flat normal
+    pub(crate) fn write_obj(&self, out: &mut impl Write, mtl_name: &str) -> std::io::Result<()> {
+        writeln!(out, "mtllib {mtl_name}")?;
+        // OBJ counts vertices and normals from 1 across the whole file, and every quad has four
+        // vertices and one normal
+        let mut quad_count = 0;
+        for piece in &self.pieces {
+            writeln!(out, "o {}", piece.name())?;
+            for material in Material::ALL {
+                let quads: Vec<&Quad> = piece
+                    .quads
+                    .iter()
+                    .filter(|quad| quad.material == material)
+                    .collect();
+                if quads.is_empty() {
+                    continue;
+                }
+                writeln!(out, "usemtl {}", material.name())?;
+                for quad in quads {
+                    for [x, y, z] in quad.corners {
+                        writeln!(out, "v {x:.6} {y:.6} {z:.6}")?;
+                    }
+                    let [x, y, z] = quad.normal;
+                    writeln!(out, "vn {x} {y} {z}")?;
+                    quad_count += 1;
+                    let first = 4 * quad_count - 3;
+                    let corners: Vec<String> = (first..first + 4)
+                        .map(|vertex| format!("{vertex}//{quad_count}"))
+                        .collect();
+                    writeln!(out, "f {}", corners.join(" "))?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // The colors as plain diffuse materials
+    pub(crate) fn write_mtl(&self, out: &mut impl Write) -> std::io::Result<()> {
+        for (material, color) in Material::ALL.iter().zip(self.colors) {
+            let [r, g, b] = color.map(|c| f64::from(c) / 255.0);
+            writeln!(out, "newmtl {}", material.name())?;
+            writeln!(out, "Kd {r:.4} {g:.4} {b:.4}")?;
+            writeln!(out, "Ks 0 0 0")?;
+            writeln!(out, "illum 1")?;
+            writeln!(out)?;
+        }
+        Ok(())
+    }
+
+    // A glTF file with everything in it, the vertex data going in as a base64 data URI
+    // There is a node and a mesh for every piece, with one primitive for each material on it, all
+    // under a root node for the whole cube
+    pub(crate) fn to_gltf(&self) -> String {
+        // Step 1: Lay out the positions, normals and indices of every primitive one after another,
+        // each in its own part of the buffer
+        let (mut positions, mut normals, mut indices) = (vec![], vec![], vec![]);
+        let mut accessors = vec![];
+        let mut meshes = vec![];
+        for piece in &self.pieces {
+            let mut primitives = vec![];
+            for (material_index, material) in Material::ALL.iter().enumerate() {
+                let quads: Vec<&Quad> = piece
+                    .quads
+                    .iter()
+                    .filter(|quad| quad.material == *material)
+                    .collect();
+                if quads.is_empty() {
+                    continue;
+                }
+                let (vertex_offset, index_offset) = (positions.len() * 4, indices.len() * 4);
+                let (mut min, mut max) = ([f32::MAX; 3], [f32::MIN; 3]);
+                for (i, quad) in quads.iter().enumerate() {
+                    for corner in quad.corners {
+                        let corner = corner.map(|c| c as f32);
+                        for axis in 0..3 {
+                            min[axis] = min[axis].min(corner[axis]);
+                            max[axis] = max[axis].max(corner[axis]);
+                        }
+                        positions.extend(corner);
+                        normals.extend(quad.normal.map(|c| c as f32));
+                    }
+                    let first = 4 * i as u32;
+                    indices.extend([0, 1, 2, 0, 2, 3].map(|corner| first + corner));
+                }
+                let vertices = quads.len() * 4;
+                let accessor = accessors.len();
+                accessors.push(format!(
+                    r#"{{"bufferView":0,"byteOffset":{vertex_offset},"componentType":{GLTF_FLOAT},"count":{vertices},"type":"VEC3","min":{},"max":{}}}"#,
+                    json_floats(&min),
+                    json_floats(&max)
+                ));
+                accessors.push(format!(
+                    r#"{{"bufferView":1,"byteOffset":{vertex_offset},"componentType":{GLTF_FLOAT},"count":{vertices},"type":"VEC3"}}"#
+                ));
+                accessors.push(format!(
+                    r#"{{"bufferView":2,"byteOffset":{index_offset},"componentType":{GLTF_UNSIGNED_INT},"count":{},"type":"SCALAR"}}"#,
+                    quads.len() * 6
+                ));
+                primitives.push(format!(
+                    r#"{{"attributes":{{"POSITION":{},"NORMAL":{}}},"indices":{},"material":{material_index}}}"#,
+                    accessor,
+                    accessor + 1,
+                    accessor + 2
+                ));
+            }
+            meshes.push(format!(
+                r#"{{"name":"{}","primitives":[{}]}}"#,
+                piece.name(),
+                primitives.join(",")
+            ));
+        }
+        let mut buffer = vec![];
+        for value in positions.iter().chain(&normals) {
+            buffer.extend(value.to_le_bytes());
+        }
+        for index in &indices {
+            buffer.extend(index.to_le_bytes());
+        }
+        let vertex_bytes = positions.len() * 4;
+        // Step 2: The nodes, one for each piece and the root holding them all
+        let mut nodes: Vec<String> = self
+            .pieces
+            .iter()
+            .enumerate()
+            .map(|(i, piece)| format!(r#"{{"name":"{}","mesh":{i}}}"#, piece.name()))
+            .collect();
+        let children: Vec<String> = (0..self.pieces.len()).map(|i| i.to_string()).collect();
+        nodes.push(format!(
+            r#"{{"name":"cube","children":[{}]}}"#,
+            children.join(",")
+        ));
+        // Step 3: glTF colors are linear, so they are taken out of sRGB first
+        let materials: Vec<String> = Material::ALL
+            .iter()
+            .zip(self.colors)
+            .map(|(material, color)| {
+                let [r, g, b] = color.map(srgb_to_linear);
+                format!(
+                    r#"{{"name":"{}","pbrMetallicRoughness":{{"baseColorFactor":[{r:.4},{g:.4},{b:.4},1],"metallicFactor":0,"roughnessFactor":0.6}}}}"#,
+                    material.name()
+                )
+            })
+            .collect();
+        let mut gltf = String::new();
+        // Writing to a String can't fail
+        let _ = write!(
+            gltf,
+            r#"{{"asset":{{"version":"2.0","generator":"RubiksCube"}},"scene":0,"scenes":[{{"nodes":[{}]}}],"nodes":[{}],"meshes":[{}],"materials":[{}],"accessors":[{}],"#,
+            self.pieces.len(),
+            nodes.join(","),
+            meshes.join(","),
+            materials.join(","),
+            accessors.join(",")
+        );
+        let _ = writeln!(
+            gltf,
+            r#""bufferViews":[{{"buffer":0,"byteOffset":0,"byteLength":{vertex_bytes},"target":{GLTF_ARRAY_BUFFER}}},{{"buffer":0,"byteOffset":{vertex_bytes},"byteLength":{vertex_bytes},"target":{GLTF_ARRAY_BUFFER}}},{{"buffer":0,"byteOffset":{},"byteLength":{},"target":{GLTF_ELEMENT_ARRAY_BUFFER}}}],"buffers":[{{"byteLength":{},"uri":"data:application/octet-stream;base64,{}"}}]}}"#,
+            2 * vertex_bytes,
+            indices.len() * 4,
+            buffer.len(),
+            base64(&buffer)
+        );
+        gltf
+    }
+}
+
+fn json_floats(values: &[f32; 3]) -> String {
+    format!("[{},{},{}]", values[0], values[1], values[2])
+}
+
+fn srgb_to_linear(value: u8) -> f64 {
+    let value = f64::from(value) / 255.0;
+    if value <= 0.04045 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+// Standard base64 with padding
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut text = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let word = chunk.iter().enumerate().fold(0u32, |word, (i, byte)| {
+            word | u32::from(*byte) << (16 - 8 * i)
+        });
+        for i in 0..4 {
+            if i <= chunk.len() {
+                text.push(ALPHABET[(word >> (18 - 6 * i) & 63) as usize] as char);
+            } else {
+                text.push('=');
+            }
+        }
+    }
+    text
+}
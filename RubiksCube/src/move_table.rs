@@ -0,0 +1,264 @@
+use std::io::{Read, Write};
+
+use crate::{CubeError, HashTracker, RubiksColor, RubiksCube, Sticker, SuperCube, TurnDirection};
+
+// Precomputed moves for one size of cube
+// Every move only shuffles stickers around, so it can be worked out once as a list of which
+// sticker indices trade places and then replayed on any cube of that size by lookup alone
+
+// Marks the start of a saved table, followed by the format version
+const MAGIC: &[u8; 4] = b"RCMT";
+const VERSION: u8 = 1;
+
+// The biggest cube a table is made for
+// A table grows with the cube of its size, and a saved table's header can't be trusted to ask for
+// a reasonable amount of memory before anything after it has been read
+pub(crate) const MAX_TABLE_SIZE: usize = 64;
+
+// One move stored as the rings of sticker indices it moves along
+// A quarter turn sends every sticker around its axis and back in four steps, except for the
+// middle of a turning face which just spins in place, so those are the only two shapes needed
+// Within a ring each spot receives the sticker from the spot before it, and the first spot
+// receives the sticker from the last one, spun by the twist stored for the spot it lands on
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+struct TableMove {
+    rings: Vec<([u32; 4], [u8; 4])>,
+    spins: Vec<(u32, u8)>,
+}
+
+impl TableMove {
+    // Works out a move by doing it to a supercube and looking at where each sticker came from
+    fn record(face_size: usize, face: RubiksColor, layer: usize, direction: TurnDirection) -> Self {
+        // Step 1: Find where the sticker now at each index started and how far it was spun
+        let mut cube = SuperCube::solved(face_size);
+        cube.apply_move(face, layer, direction);
+        let sources: Vec<usize> = cube
+            .stickers
+            .iter()
+            .map(|s| cube.sticker_index(s.id.face, s.id.x, s.id.y))
+            .collect();
+        let twist = |spot: usize| cube.stickers[spot].orientation;
+        // Step 2: Walk each ring backwards from a sticker that moved, then flip it around so it
+        // reads in the direction the stickers travel
+        let mut table_move = Self::default();
+        let mut visited = vec![false; sources.len()];
+        for start in 0..sources.len() {
+            if visited[start] {
+                continue;
+            }
+            if sources[start] == start {
+                if twist(start) != 0 {
+                    table_move.spins.push((start as u32, twist(start)));
+                }
+                continue;
+            }
+            let mut ring = [0; 4];
+            let mut spot = start;
+            for k in (0..4).rev() {
+                visited[spot] = true;
+                ring[k] = spot as u32;
+                spot = sources[spot];
+            }
+            table_move
+                .rings
+                .push((ring, ring.map(|spot| twist(spot as usize))));
+        }
+        table_move
+    }
+
+    // Moves the stickers, keeping the hash up to date if the cube has one
+    fn apply<S: Sticker>(&self, stickers: &mut [S], mut tracker: Option<HashTracker>) {
+        for (ring, twists) in &self.rings {
+            let spots = ring.map(|spot| spot as usize);
+            let [a, b, c, d] = spots;
+            let before = spots.map(|spot| stickers[spot]);
+            stickers[d] = before[2].turned(twists[3]);
+            stickers[c] = before[1].turned(twists[2]);
+            stickers[b] = before[0].turned(twists[1]);
+            stickers[a] = before[3].turned(twists[0]);
+            if let Some(tracker) = tracker.as_mut() {
+                for k in 0..4 {
+                    tracker.replace(spots[k], before[k], stickers[spots[k]]);
+                }
+            }
+        }
+        for (spot, twist) in &self.spins {
+            let spot = *spot as usize;
+            let before = stickers[spot];
+            stickers[spot] = before.turned(*twist);
+            if let Some(tracker) = tracker.as_mut() {
+                tracker.replace(spot, before, stickers[spot]);
+            }
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct MoveTable {
+    face_size: usize,
+    moves: Vec<TableMove>, // Indexed by move_index
+}
+
+impl MoveTable {
+    // Works out every move on a cube of the given size
+    // There are 12 moves per layer, each touching about a face worth of stickers, so this gets big
+    // quickly and is meant for the smaller cubes that get searched
+    pub(crate) fn new(face_size: usize) -> Result<Self, CubeError> {
+        check_size(face_size)?;
+        let mut moves = vec![];
+        for face in RubiksColor::ALL {
+            for layer in 0..face_size {
+                for direction in [TurnDirection::CLOCKWISE, TurnDirection::COUNTERCLOCKWISE] {
+                    moves.push(TableMove::record(face_size, face, layer, direction));
+                }
+            }
+        }
+        Ok(Self { face_size, moves })
+    }
+
+    // Where a move is kept in the table, laid out face by face, then layer by layer
+    fn move_index(
+        &self,
+        face: RubiksColor,
+        layer: usize,
+        direction: TurnDirection,
+    ) -> Result<usize, CubeError> {
+        if layer >= self.face_size {
+            return Err(CubeError::InvalidLayer {
+                layer,
+                face_size: self.face_size,
+            });
+        }
+        let turn = match direction {
+            TurnDirection::CLOCKWISE => 0,
+            TurnDirection::COUNTERCLOCKWISE => 1,
+        };
+        Ok((face.index() * self.face_size + layer) * 2 + turn)
+    }
+
+    // Preform a move on a cube in place by looking it up, same as RubiksCube::try_apply_move
+    pub(crate) fn try_apply_move<S: Sticker>(
+        &self,
+        cube: &mut RubiksCube<S>,
+        face: RubiksColor,
+        layer: usize,
+        direction: TurnDirection,
+    ) -> Result<(), CubeError> {
+        if cube.face_size != self.face_size {
+            return Err(CubeError::WrongSize {
+                face_size: cube.face_size,
+                expected: self.face_size,
+            });
+        }
+        let index = self.move_index(face, layer, direction)?;
+        let tracker = HashTracker {
+            hash: &mut cube.hash,
+            first_index: 0,
+        };
+        self.moves[index].apply(&mut cube.stickers, Some(tracker));
+        Ok(())
+    }
+
+    // Writes the table out so it doesn't have to be worked out again
+    // The format is the magic bytes, a version byte and the face size, then for each move its ring
+    // count and rings followed by its spin count and spins, each sticker written as an index and a
+    // twist, all little endian
+    pub(crate) fn write_to(&self, out: &mut impl Write) -> Result<(), CubeError> {
+        out.write_all(MAGIC)?;
+        out.write_all(&[VERSION])?;
+        out.write_all(&(self.face_size as u32).to_le_bytes())?;
+        for table_move in &self.moves {
+            out.write_all(&(table_move.rings.len() as u32).to_le_bytes())?;
+            for (ring, twists) in &table_move.rings {
+                for k in 0..4 {
+                    write_sticker(out, ring[k], twists[k])?;
+                }
+            }
+            out.write_all(&(table_move.spins.len() as u32).to_le_bytes())?;
+            for (spot, twist) in &table_move.spins {
+                write_sticker(out, *spot, *twist)?;
+            }
+        }
+        Ok(())
+    }
+
+    // Reads a table back in, checking that it really is a table of moves for its cube size
+    pub(crate) fn read_from(input: &mut impl Read) -> Result<Self, CubeError> {
+        let error = |message: &str| CubeError::Parse(format!("move table: {message}"));
+        // Step 1: Check the header
+        let mut magic = [0; 4];
+        input.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(error("not a move table file"));
+        }
+        let version = read_u8(input)?;
+        if version != VERSION {
+            return Err(error(&format!("unsupported version {version}")));
+        }
+        let face_size = read_u32(input)? as usize;
+        check_size(face_size)?;
+        // Step 2: Read every move, making sure each one only moves stickers that exist and moves
+        // each of them at most once
+        let sticker_count = 6 * face_size * face_size;
+        let mut moves = vec![];
+        for _ in 0..12 * face_size {
+            let mut table_move = TableMove::default();
+            let mut used = vec![false; sticker_count];
+            let mut read_sticker = |input: &mut dyn Read| -> Result<(u32, u8), CubeError> {
+                let spot = read_u32(input)?;
+                let twist = read_u8(input)?;
+                let seen = used
+                    .get_mut(spot as usize)
+                    .ok_or_else(|| error("sticker index off the cube"))?;
+                if *seen || twist > 3 {
+                    return Err(error("move isn't a turn of the stickers"));
+                }
+                *seen = true;
+                Ok((spot, twist))
+            };
+            for _ in 0..read_u32(input)? {
+                let mut ring = [0; 4];
+                let mut twists = [0; 4];
+                for k in 0..4 {
+                    (ring[k], twists[k]) = read_sticker(input)?;
+                }
+                table_move.rings.push((ring, twists));
+            }
+            for _ in 0..read_u32(input)? {
+                table_move.spins.push(read_sticker(input)?);
+            }
+            moves.push(table_move);
+        }
+        Ok(Self { face_size, moves })
+    }
+}
+
+fn check_size(face_size: usize) -> Result<(), CubeError> {
+    if face_size == 0 {
+        return Err(CubeError::InvalidSize(face_size));
+    }
+    if face_size > MAX_TABLE_SIZE {
+        return Err(CubeError::TooLarge(format!(
+            "a move table for a {face_size}x{face_size} cube"
+        )));
+    }
+    Ok(())
+}
+
+fn write_sticker(out: &mut impl Write, spot: u32, twist: u8) -> Result<(), CubeError> {
+    out.write_all(&spot.to_le_bytes())?;
+    out.write_all(&[twist])?;
+    Ok(())
+}
+
+fn read_u8(input: &mut (impl Read + ?Sized)) -> Result<u8, CubeError> {
+    let mut bytes = [0; 1];
+    input.read_exact(&mut bytes)?;
+    Ok(bytes[0])
+}
+
+fn read_u32(input: &mut (impl Read + ?Sized)) -> Result<u32, CubeError> {
+    let mut bytes = [0; 4];
+    input.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
@@ -0,0 +1,262 @@
+use std::fmt;
+use std::io::IsTerminal;
+
+use crate::{read_2d_vec, RubiksColor, RubiksCube};
+
+// Drawing a cube as text, unfolded into the usual cross
+//   U
+// L F R B
+//   D
+// Every sticker is one letter, or a colored block on terminals that can show colors, laid out the
+// way the face is stored, and faces next to each other are separated by a space
+
+// The widest a colored sticker gets, two columns being about square in most terminals
+const MAX_STICKER_WIDTH: usize = 2;
+
+// How wide the terminal is assumed to be when it doesn't say
+const DEFAULT_COLUMNS: usize = 80;
+
+// The faces across the middle row of the net, left to right
+const MIDDLE_ROW: [RubiksColor; 4] = [
+    RubiksColor::LEFT,
+    RubiksColor::FORWARD,
+    RubiksColor::RIGHT,
+    RubiksColor::BACK,
+];
+
+// The letter each color is drawn with
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct NetLetters {
+    letters: [char; 6], // In the same order as RubiksColor::ALL
+}
+
+impl NetLetters {
+    // The face letters from move notation, so a solved cube has U all over the up face
+    pub(crate) const FACES: NetLetters = NetLetters {
+        letters: ['U', 'D', 'L', 'R', 'F', 'B'],
+    };
+
+    // The usual color scheme, with white on top and green in front
+    pub(crate) const COLORS: NetLetters = NetLetters {
+        letters: ['W', 'Y', 'O', 'R', 'G', 'B'],
+    };
+
+    pub(crate) fn letter(&self, color: RubiksColor) -> char {
+        self.letters[color.index()]
+    }
+}
+
+impl Default for NetLetters {
+    fn default() -> Self {
+        NetLetters::FACES
+    }
+}
+
+// A cube ready to be printed as a net with a particular set of letters
+pub(crate) struct Net<'a> {
+    cube: &'a RubiksCube,
+    letters: NetLetters,
+}
+
+impl RubiksCube {
+    pub(crate) fn net(&self, letters: NetLetters) -> Net<'_> {
+        Net {
+            cube: self,
+            letters,
+        }
+    }
+}
+
+impl fmt::Display for Net<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write_net(f, self.cube, 1, |f, color| {
+            write!(f, "{}", self.letters.letter(color))
+        })
+    }
+}
+
+// Lays out the net, with sticker drawing each sticker sticker_width columns wide
+fn write_net(
+    f: &mut fmt::Formatter,
+    cube: &RubiksCube,
+    sticker_width: usize,
+    mut sticker: impl FnMut(&mut fmt::Formatter, RubiksColor) -> fmt::Result,
+) -> fmt::Result {
+    let n = cube.face_size;
+    // The up and down faces sit over and under the front face, one face and a space in
+    let indent = " ".repeat(n * sticker_width + 1);
+    let bands: [(&str, &[RubiksColor]); 3] = [
+        (&indent, &[RubiksColor::UP]),
+        ("", &MIDDLE_ROW),
+        (&indent, &[RubiksColor::DOWN]),
+    ];
+    for (band, (indent, faces)) in bands.into_iter().enumerate() {
+        for y in 0..n {
+            if band > 0 || y > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{indent}")?;
+            for (i, face) in faces.iter().enumerate() {
+                if i > 0 {
+                    write!(f, " ")?;
+                }
+                for x in 0..n {
+                    if let Some(color) = read_2d_vec(cube.face(*face), &n, &x, &y) {
+                        sticker(f, *color)?;
+                    }
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+// The red, green and blue each color is shown with
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct ColorScheme {
+    colors: [[u8; 3]; 6], // In the same order as RubiksColor::ALL
+}
+
+impl ColorScheme {
+    // White on top, green in front, matching NetLetters::COLORS
+    pub(crate) const STANDARD: ColorScheme = ColorScheme {
+        colors: [
+            [255, 255, 255],
+            [255, 213, 0],
+            [255, 88, 0],
+            [183, 18, 52],
+            [0, 155, 72],
+            [0, 70, 173],
+        ],
+    };
+
+    pub(crate) fn rgb(&self, color: RubiksColor) -> [u8; 3] {
+        self.colors[color.index()]
+    }
+}
+
+impl Default for ColorScheme {
+    fn default() -> Self {
+        ColorScheme::STANDARD
+    }
+}
+
+// How much color the terminal can show
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
+pub(crate) enum ColorMode {
+    TrueColor,  // Any 24 bit color
+    Palette256, // The standard 256 color palette, colors are rounded to the nearest one
+    Plain,      // No color at all, stickers are drawn as letters
+}
+
+impl ColorMode {
+    // Works out what standard output can show from the usual environment variables
+    // Anything that isn't a terminal, or asks for no color, gets plain letters
+    pub(crate) fn detect() -> ColorMode {
+        let var = |name| std::env::var(name).unwrap_or_default();
+        if !std::io::stdout().is_terminal() || std::env::var_os("NO_COLOR").is_some() {
+            return ColorMode::Plain;
+        }
+        let term = var("TERM");
+        if matches!(var("COLORTERM").as_str(), "truecolor" | "24bit") {
+            ColorMode::TrueColor
+        } else if term.contains("256color") {
+            ColorMode::Palette256
+        } else {
+            ColorMode::Plain
+        }
+    }
+}
+
+// A cube ready to be printed as a net of colored blocks
+pub(crate) struct ColorNet<'a> {
+    cube: &'a RubiksCube,
+    scheme: ColorScheme,
+    mode: ColorMode,
+    sticker_width: usize,
+}
+
+impl RubiksCube {
+    pub(crate) fn color_net(&self, scheme: ColorScheme, mode: ColorMode) -> ColorNet<'_> {
+        ColorNet {
+            cube: self,
+            scheme,
+            mode,
+            sticker_width: MAX_STICKER_WIDTH,
+        }
+    }
+}
+
+impl ColorNet<'_> {
+    // Makes the stickers narrower until the net fits in this many columns, or they are down to
+    // one column each
+    pub(crate) fn fit_width(mut self, columns: usize) -> Self {
+        // Four faces across with a space between each
+        let per_sticker = columns.saturating_sub(3) / (4 * self.cube.face_size).max(1);
+        self.sticker_width = per_sticker.clamp(1, MAX_STICKER_WIDTH);
+        self
+    }
+
+    // Fits the net to the terminal, going by the COLUMNS variable most shells set
+    pub(crate) fn fit_terminal(self) -> Self {
+        let columns = std::env::var("COLUMNS")
+            .ok()
+            .and_then(|columns| columns.parse().ok())
+            .unwrap_or(DEFAULT_COLUMNS);
+        self.fit_width(columns)
+    }
+}
+
+impl fmt::Display for ColorNet<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let blank = " ".repeat(self.sticker_width);
+        write_net(f, self.cube, self.sticker_width, |f, color| {
+            let [r, g, b] = self.scheme.rgb(color);
+            // Each sticker resets the color after itself, so the gaps stay the terminal's own
+            match self.mode {
+                ColorMode::TrueColor => write!(f, "\x1b[48;2;{r};{g};{b}m{blank}\x1b[0m"),
+                ColorMode::Palette256 => {
+                    write!(f, "\x1b[48;5;{}m{blank}\x1b[0m", palette_index([r, g, b]))
+                }
+                ColorMode::Plain => {
+                    let letter = NetLetters::COLORS.letter(color);
+                    write!(f, "{letter:<width$}", width = self.sticker_width)
+                }
+            }
+        })
+    }
+}
+
+// The closest color in the 256 color palette, out of its 6x6x6 color cube and its 24 grays
+pub(crate) fn palette_index(rgb: [u8; 3]) -> u8 {
+    const LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+    let distance = |other: [u8; 3]| -> u32 {
+        rgb.iter()
+            .zip(other)
+            .map(|(a, b)| (i32::from(*a) - i32::from(b)).pow(2) as u32)
+            .sum()
+    };
+    let nearest_level = |value: u8| {
+        (0..6)
+            .min_by_key(|i| (i32::from(LEVELS[*i]) - i32::from(value)).abs())
+            .unwrap()
+    };
+    let [r, g, b] = rgb.map(nearest_level);
+    let cube = (16 + 36 * r + 6 * g + b) as u8;
+    let cube_color = [LEVELS[r], LEVELS[g], LEVELS[b]];
+    // The grays run from 8 to 238 in steps of 10
+    let average = rgb.iter().map(|c| u32::from(*c)).sum::<u32>() / 3;
+    let gray_step = (average.saturating_sub(3) / 10).min(23) as u8;
+    let gray = 8 + 10 * gray_step;
+    if distance([gray; 3]) < distance(cube_color) {
+        232 + gray_step
+    } else {
+        cube
+    }
+}
+
+impl fmt::Display for RubiksCube {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.net(NetLetters::default()).fmt(f)
+    }
+}
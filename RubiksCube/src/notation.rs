@@ -0,0 +1,224 @@
+use std::fmt;
+use std::str::FromStr;
+
+use crate::{CubeError, RubiksColor, RubiksCube, Sticker, TurnDirection};
+
+// Code for reading and writing cubes and moves as text
+
+// Faces are written in the order most cube software uses for facelet strings
+const FACELET_ORDER: [RubiksColor; 6] = [
+    RubiksColor::UP,
+    RubiksColor::RIGHT,
+    RubiksColor::FORWARD,
+    RubiksColor::DOWN,
+    RubiksColor::LEFT,
+    RubiksColor::BACK,
+];
+
+impl RubiksColor {
+    // The letter used for this face in move notation and facelet strings
+    pub(crate) fn letter(&self) -> char {
+        match self {
+            RubiksColor::UP => 'U',
+            RubiksColor::DOWN => 'D',
+            RubiksColor::LEFT => 'L',
+            RubiksColor::RIGHT => 'R',
+            RubiksColor::FORWARD => 'F',
+            RubiksColor::BACK => 'B',
+        }
+    }
+
+    pub(crate) fn from_letter(letter: char) -> Result<RubiksColor, CubeError> {
+        RubiksColor::ALL
+            .into_iter()
+            .find(|face| face.letter() == letter)
+            .ok_or_else(|| CubeError::Parse(format!("'{letter}' as a face")))
+    }
+}
+
+// A single turn of one layer, written like R, U', F2 or 3R'
+// The number in front counts layers in from the face starting at 1, so 2R is the slice next to R
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct Move {
+    pub(crate) face: RubiksColor,
+    pub(crate) layer: usize,
+    pub(crate) direction: TurnDirection,
+    pub(crate) double: bool, // Half turns are the same quarter turn done twice
+}
+
+impl Move {
+    pub(crate) fn new(face: RubiksColor, layer: usize, direction: TurnDirection) -> Self {
+        Self {
+            face,
+            layer,
+            direction,
+            double: false,
+        }
+    }
+
+    // The move that undoes this one
+    pub(crate) fn inverse(&self) -> Move {
+        Move {
+            direction: self.direction.opposite(),
+            ..*self
+        }
+    }
+}
+
+impl FromStr for Move {
+    type Err = CubeError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let error = || CubeError::Parse(format!("\"{s}\" as a move"));
+        // Step 1: Split off the layer number, if there is one
+        let digits = s.chars().take_while(|c| c.is_ascii_digit()).count();
+        let layer = match &s[..digits] {
+            "" => 0,
+            number => match number.parse::<usize>() {
+                Ok(layer) if layer > 0 => layer - 1,
+                _ => return Err(error()),
+            },
+        };
+        // Step 2: Read the face being turned
+        let mut rest = s[digits..].chars();
+        let face = RubiksColor::from_letter(rest.next().ok_or_else(error)?).map_err(|_| error())?;
+        // Step 3: Read how far it is being turned
+        let (direction, double) = match rest.as_str() {
+            "" => (TurnDirection::CLOCKWISE, false),
+            "'" => (TurnDirection::COUNTERCLOCKWISE, false),
+            "2" => (TurnDirection::CLOCKWISE, true),
+            "2'" => (TurnDirection::COUNTERCLOCKWISE, true),
+            _ => return Err(error()),
+        };
+        Ok(Self {
+            face,
+            layer,
+            direction,
+            double,
+        })
+    }
+}
+
+impl fmt::Display for Move {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.layer > 0 {
+            write!(f, "{}", self.layer + 1)?;
+        }
+        write!(f, "{}", self.face.letter())?;
+        if self.double {
+            write!(f, "2")?;
+        }
+        if self.direction == TurnDirection::COUNTERCLOCKWISE {
+            write!(f, "'")?;
+        }
+        Ok(())
+    }
+}
+
+// A sequence of moves separated by whitespace
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub(crate) struct Algorithm {
+    pub(crate) moves: Vec<Move>,
+}
+
+impl Algorithm {
+    // The moves that undo this algorithm, so doing it to a solved cube sets up the case this
+    // algorithm solves
+    pub(crate) fn inverse(&self) -> Algorithm {
+        Algorithm {
+            moves: self.moves.iter().rev().map(Move::inverse).collect(),
+        }
+    }
+}
+
+impl FromStr for Algorithm {
+    type Err = CubeError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let moves = s
+            .split_whitespace()
+            .map(Move::from_str)
+            .collect::<Result<_, _>>()?;
+        Ok(Self { moves })
+    }
+}
+
+impl fmt::Display for Algorithm {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, m) in self.moves.iter().enumerate() {
+            if i > 0 {
+                write!(f, " ")?;
+            }
+            write!(f, "{m}")?;
+        }
+        Ok(())
+    }
+}
+
+impl<S: Sticker> RubiksCube<S> {
+    // Plays every move of an algorithm in order on a copy of this cube
+    // Stops at the first move that doesn't fit on the cube
+    pub(crate) fn try_do_algorithm(
+        &self,
+        algorithm: &Algorithm,
+    ) -> Result<RubiksCube<S>, CubeError> {
+        let mut cube = self.clone();
+        for m in &algorithm.moves {
+            let turns = if m.double { 2 } else { 1 };
+            for _ in 0..turns {
+                cube.try_apply_move(m.face, m.layer, m.direction)?;
+            }
+        }
+        Ok(cube)
+    }
+}
+
+impl RubiksCube {
+    // Reads a cube from its facelet string
+    // The string has every face in URFDLB order, each written row by row the same way the faces
+    // are stored, using the face letters as colors. Whitespace is ignored
+    pub(crate) fn from_facelets(facelets: &str) -> Result<Self, CubeError> {
+        // Step 1: Turn the letters into colors
+        let colors = facelets
+            .chars()
+            .filter(|c| !c.is_whitespace())
+            .map(RubiksColor::from_letter)
+            .collect::<Result<Vec<_>, _>>()?;
+        // Step 2: Work out the size of the cube from how many facelets there are
+        let face_squares = colors.len() / 6;
+        let face_size = (0..=face_squares)
+            .find(|size| size * size >= face_squares)
+            .unwrap_or(0);
+        if face_size == 0 || colors.len() != 6 * face_size * face_size {
+            return Err(CubeError::InvalidState(format!(
+                "{} facelets can't cover six square faces",
+                colors.len()
+            )));
+        }
+        // Step 3: Make sure every color shows up exactly as often as it does on a solved cube
+        for face in RubiksColor::ALL {
+            let count = colors.iter().filter(|c| **c == face).count();
+            if count != face_squares {
+                return Err(CubeError::InvalidState(format!(
+                    "{} has {count} stickers instead of {face_squares}",
+                    face.letter()
+                )));
+            }
+        }
+        // Step 4: Each face's facelets are already in the order the face stores them
+        let mut cube = Self::new(face_size);
+        for (face, stickers) in FACELET_ORDER.iter().zip(colors.chunks(face_squares)) {
+            cube.face_mut(*face).copy_from_slice(stickers);
+        }
+        cube.rehash();
+        Ok(cube)
+    }
+
+    // Writes the cube out in the same format from_facelets reads
+    pub(crate) fn to_facelets(&self) -> String {
+        FACELET_ORDER
+            .iter()
+            .flat_map(|face| self.face(*face).iter().map(|c| c.letter()))
+            .collect()
+    }
+}
@@ -0,0 +1,69 @@
+use crate::{CubeError, RubiksColor, RubiksCube};
+
+// A small cube squeezed into a few machine words so it can be copied and hashed cheaply
+// Each sticker takes 3 bits, so a 3x3's 54 stickers fit in three u64s
+
+const BITS_PER_STICKER: usize = 3;
+const STICKERS_PER_WORD: usize = 64 / BITS_PER_STICKER;
+const WORDS: usize = 3;
+
+// The largest cube whose stickers all fit in the packed words
+pub(crate) const MAX_PACKED_SIZE: usize = 3;
+
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
+pub(crate) struct PackedCube {
+    face_size: u8,
+    bits: [u64; WORDS], // Stickers in the same order the full cube stores them
+}
+
+impl PackedCube {
+    pub(crate) fn face_size(&self) -> usize {
+        self.face_size as usize
+    }
+
+    fn read(&self, index: usize) -> RubiksColor {
+        let shift = (index % STICKERS_PER_WORD) * BITS_PER_STICKER;
+        let value = (self.bits[index / STICKERS_PER_WORD] >> shift) & 0b111;
+        RubiksColor::ALL[value as usize]
+    }
+
+    fn write(&mut self, index: usize, color: RubiksColor) {
+        let shift = (index % STICKERS_PER_WORD) * BITS_PER_STICKER;
+        let word = &mut self.bits[index / STICKERS_PER_WORD];
+        *word = (*word & !(0b111 << shift)) | ((color.index() as u64) << shift);
+    }
+}
+
+impl TryFrom<&RubiksCube> for PackedCube {
+    type Error = CubeError;
+
+    fn try_from(cube: &RubiksCube) -> Result<Self, Self::Error> {
+        let n = cube.face_size;
+        if n == 0 {
+            return Err(CubeError::InvalidSize(n));
+        }
+        if n > MAX_PACKED_SIZE {
+            return Err(CubeError::TooLarge(format!("a packed {n}x{n} cube")));
+        }
+        let mut packed = PackedCube {
+            face_size: n as u8,
+            bits: [0; WORDS],
+        };
+        for (index, color) in cube.stickers.iter().enumerate() {
+            packed.write(index, *color);
+        }
+        Ok(packed)
+    }
+}
+
+impl From<PackedCube> for RubiksCube {
+    fn from(packed: PackedCube) -> Self {
+        let face_size = packed.face_size();
+        RubiksCube::from_stickers(
+            face_size,
+            (0..6 * face_size * face_size)
+                .map(|index| packed.read(index))
+                .collect(),
+        )
+    }
+}
@@ -0,0 +1,78 @@
+use std::ops::Range;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+// Helpers for spreading work over several threads while still getting the same answer as doing
+// it all on one
+
+// How many indices a thread takes at a time in for_each_chunk
+const CHUNK_SIZE: usize = 1 << 16;
+
+// One thread per core, for when nobody asks for a particular number
+pub(crate) fn default_threads() -> usize {
+    std::thread::available_parallelism().map_or(1, usize::from)
+}
+
+// Runs f on each task and returns the answer from the earliest task that has one, which is the
+// answer going through the tasks one at a time would give
+// Threads take the tasks in order, and once a task has an answer every later task is called off.
+// f is handed a check that says whether its task has been called off, and should give up with
+// None when it has
+pub(crate) fn first_in_order<T: Sync, R: Send>(
+    threads: usize,
+    tasks: &[T],
+    f: impl Fn(&T, &dyn Fn() -> bool) -> Option<R> + Sync,
+) -> Option<R> {
+    let next = AtomicUsize::new(0);
+    let found = AtomicUsize::new(usize::MAX); // The earliest task with an answer so far
+    let answers = Mutex::new(vec![]);
+    let work = || loop {
+        let index = next.fetch_add(1, Ordering::Relaxed);
+        if index >= tasks.len() || index > found.load(Ordering::Relaxed) {
+            break;
+        }
+        let cancelled = || found.load(Ordering::Relaxed) < index;
+        if let Some(answer) = f(&tasks[index], &cancelled) {
+            found.fetch_min(index, Ordering::Relaxed);
+            answers.lock().unwrap().push((index, answer));
+        }
+    };
+    if threads <= 1 {
+        work();
+    } else {
+        std::thread::scope(|scope| {
+            for _ in 0..threads.min(tasks.len()) {
+                scope.spawn(work);
+            }
+        });
+    }
+    answers
+        .into_inner()
+        .unwrap()
+        .into_iter()
+        .min_by_key(|(index, _)| *index)
+        .map(|(_, answer)| answer)
+}
+
+// Calls f on pieces of 0..len until all of it is covered, spread over the threads
+// Which thread gets which piece changes from run to run, so f has to give the same result
+// whatever order the pieces are done in
+pub(crate) fn for_each_chunk(threads: usize, len: usize, f: impl Fn(Range<usize>) + Sync) {
+    let next = AtomicUsize::new(0);
+    let work = || loop {
+        let start = next.fetch_add(CHUNK_SIZE, Ordering::Relaxed);
+        if start >= len {
+            break;
+        }
+        f(start..(start + CHUNK_SIZE).min(len));
+    };
+    if threads <= 1 {
+        work();
+    } else {
+        std::thread::scope(|scope| {
+            for _ in 0..threads.min(len.div_ceil(CHUNK_SIZE)) {
+                scope.spawn(work);
+            }
+        });
+    }
+}
@@ -0,0 +1,365 @@
+use std::fs::File;
+use std::io::{BufWriter, Read, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+
+use crate::coordinates::{face_moves, phase_two_moves, Coordinate, CoordinateTable};
+use crate::notation::Move;
+use crate::parallel;
+use crate::CubeError;
+
+// Pattern databases, tables of how many moves it takes at least to solve part of a 3x3
+// A database covers every combination of a few coordinates, and is filled in by searching
+// outwards from solved, so looking a state up never overestimates how far it is from solved
+
+// Saved databases start with the magic bytes and the format version, followed by the cube size,
+// the move set, how many coordinates there are and their ids, then the number of entries and a
+// checksum of the entries, all little endian. The entries come last, two to a byte
+const MAGIC: &[u8; 4] = b"RCPD";
+const VERSION: u8 = 1;
+
+// Distances are kept in 4 bits, and the biggest one marks states the search never reached
+// Anything not reached within 14 moves is at least 15 away, so it still never overestimates
+const UNVISITED: u8 = 15;
+
+// The moves a database counts distances with
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
+pub(crate) enum MoveSet {
+    FaceMoves, // All 18 face moves
+    PhaseTwo,  // Only the moves that keep the edges flipped right and the middle edges in place
+}
+
+impl MoveSet {
+    pub(crate) fn moves(&self) -> Vec<Move> {
+        match self {
+            MoveSet::FaceMoves => face_moves(),
+            MoveSet::PhaseTwo => phase_two_moves(),
+        }
+    }
+
+    fn id(&self) -> u8 {
+        match self {
+            MoveSet::FaceMoves => 0,
+            MoveSet::PhaseTwo => 1,
+        }
+    }
+
+    fn from_id(id: u8) -> Option<MoveSet> {
+        match id {
+            0 => Some(MoveSet::FaceMoves),
+            1 => Some(MoveSet::PhaseTwo),
+            _ => None,
+        }
+    }
+}
+
+pub(crate) struct PatternDatabase {
+    coordinates: Vec<Coordinate>,
+    move_set: MoveSet,
+    entries: usize,
+    data: TableData,
+}
+
+impl PatternDatabase {
+    // Fills in the database by a breadth first search from solved
+    // Each pass goes over the whole table looking for the states found on the last pass, so only
+    // the table itself has to fit in memory. The passes are split between threads, and since a
+    // state's distance is the same whichever thread finds it, so is the table
+    pub(crate) fn build(
+        coordinates: &[Coordinate],
+        move_set: MoveSet,
+        threads: usize,
+    ) -> Result<Self, CubeError> {
+        let tables = coordinates
+            .iter()
+            .map(|coordinate| CoordinateTable::with_moves(*coordinate, move_set.moves()))
+            .collect::<Result<Vec<_>, _>>()?;
+        let move_count = move_set.moves().len();
+        let mut database = PatternDatabase {
+            coordinates: coordinates.to_vec(),
+            move_set,
+            entries: coordinates.iter().map(Coordinate::size).product(),
+            data: TableData::Owned(vec![]),
+        };
+        let data: Vec<AtomicU8> = (0..database.entries.div_ceil(2))
+            .map(|_| AtomicU8::new(UNVISITED << 4 | UNVISITED))
+            .collect();
+        set_shared_entry(&data, 0, 0);
+        for depth in 0..UNVISITED - 1 {
+            let found = AtomicBool::new(false);
+            parallel::for_each_chunk(threads, database.entries, |indices| {
+                let mut values = vec![0; coordinates.len()];
+                for index in indices {
+                    if get_shared_entry(&data, index) != depth {
+                        continue;
+                    }
+                    database.split_index(index, &mut values);
+                    for move_index in 0..move_count {
+                        let next = tables.iter().zip(&values).fold(0, |next, (table, value)| {
+                            next * table.coordinate().size() + table.apply(*value, move_index)
+                        });
+                        if get_shared_entry(&data, next) == UNVISITED {
+                            set_shared_entry(&data, next, depth + 1);
+                            found.store(true, Ordering::Relaxed);
+                        }
+                    }
+                }
+            });
+            if !found.into_inner() {
+                break;
+            }
+        }
+        database.data = TableData::Owned(data.into_iter().map(AtomicU8::into_inner).collect());
+        Ok(database)
+    }
+
+    pub(crate) fn coordinates(&self) -> &[Coordinate] {
+        &self.coordinates
+    }
+
+    pub(crate) fn move_set(&self) -> MoveSet {
+        self.move_set
+    }
+
+    // Where a set of coordinate values is kept, with the first coordinate changing slowest
+    fn index(&self, values: &[usize]) -> usize {
+        self.coordinates
+            .iter()
+            .zip(values)
+            .fold(0, |index, (coordinate, value)| {
+                index * coordinate.size() + value
+            })
+    }
+
+    fn split_index(&self, mut index: usize, values: &mut [usize]) {
+        for (coordinate, value) in self.coordinates.iter().zip(values.iter_mut()).rev() {
+            *value = index % coordinate.size();
+            index /= coordinate.size();
+        }
+    }
+
+    // The fewest moves that could solve a state with these values of the coordinates
+    pub(crate) fn distance(&self, values: &[usize]) -> u8 {
+        get_entry(self.data.bytes(), self.index(values))
+    }
+
+    pub(crate) fn save(&self, path: impl AsRef<Path>) -> Result<(), CubeError> {
+        let mut out = BufWriter::new(File::create(path)?);
+        out.write_all(MAGIC)?;
+        out.write_all(&[VERSION, 3, self.move_set.id(), self.coordinates.len() as u8])?;
+        for coordinate in &self.coordinates {
+            out.write_all(&[coordinate.id()])?;
+        }
+        out.write_all(&(self.entries as u64).to_le_bytes())?;
+        out.write_all(&checksum(self.data.bytes()).to_le_bytes())?;
+        out.write_all(self.data.bytes())?;
+        out.flush()?;
+        Ok(())
+    }
+
+    // Opens a saved database, mapping the file into memory instead of copying it into a buffer
+    // where the system allows it
+    // The header and checksum are checked before anything is looked up, and the checksum reads
+    // every entry once, so a damaged file is caught here rather than by a wrong solve
+    pub(crate) fn load(path: impl AsRef<Path>) -> Result<Self, CubeError> {
+        let error = |message: &str| CubeError::Parse(format!("pattern database: {message}"));
+        let mut file = File::open(path)?;
+        // Step 1: Read the header
+        let mut start = [0; 8];
+        file.read_exact(&mut start)?;
+        let [m0, m1, m2, m3, version, face_size, move_set, count] = start;
+        if &[m0, m1, m2, m3] != MAGIC {
+            return Err(error("not a pattern database file"));
+        }
+        if version != VERSION {
+            return Err(error(&format!("unsupported version {version}")));
+        }
+        if face_size != 3 {
+            return Err(CubeError::WrongSize {
+                face_size: face_size as usize,
+                expected: 3,
+            });
+        }
+        let move_set = MoveSet::from_id(move_set).ok_or_else(|| error("unknown move set"))?;
+        let mut ids = vec![0; count as usize];
+        file.read_exact(&mut ids)?;
+        let coordinates = ids
+            .into_iter()
+            .map(Coordinate::from_id)
+            .collect::<Option<Vec<_>>>()
+            .ok_or_else(|| error("unknown coordinate"))?;
+        let mut numbers = [0; 16];
+        file.read_exact(&mut numbers)?;
+        let entries = u64::from_le_bytes(numbers[..8].try_into().unwrap()) as usize;
+        let expected_checksum = u64::from_le_bytes(numbers[8..].try_into().unwrap());
+        if entries != coordinates.iter().map(Coordinate::size).product() {
+            return Err(error("entry count doesn't match the coordinates"));
+        }
+        // Step 2: Map the entries and make sure they are what was saved
+        let offset = 8 + count as usize + 16;
+        let data = TableData::Mapped {
+            file: MappedFile::new(&file)?,
+            offset,
+        };
+        if data.bytes().len() != entries.div_ceil(2) {
+            return Err(error("file is the wrong length"));
+        }
+        if checksum(data.bytes()) != expected_checksum {
+            return Err(error("checksum doesn't match, the file is damaged"));
+        }
+        Ok(PatternDatabase {
+            coordinates,
+            move_set,
+            entries,
+            data,
+        })
+    }
+}
+
+fn get_entry(data: &[u8], index: usize) -> u8 {
+    (data[index / 2] >> (4 * (index % 2))) & 0xF
+}
+
+// The same while a database is being built, when several threads fill it in at once
+fn get_shared_entry(data: &[AtomicU8], index: usize) -> u8 {
+    (data[index / 2].load(Ordering::Relaxed) >> (4 * (index % 2))) & 0xF
+}
+
+// Only ever called on unvisited entries, which have every bit set, so clearing the bits the new
+// value doesn't have writes it without touching the other entry in the byte
+fn set_shared_entry(data: &[AtomicU8], index: usize, value: u8) {
+    let shift = 4 * (index % 2);
+    data[index / 2].fetch_and(!((UNVISITED ^ value) << shift), Ordering::Relaxed);
+}
+
+// 64 bit FNV-1a, plenty to notice a damaged or cut off file
+fn checksum(data: &[u8]) -> u64 {
+    data.iter().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x0000_0100_0000_01b3)
+    })
+}
+
+// The entries of a database, either built in memory or mapped from a file
+enum TableData {
+    Owned(Vec<u8>),
+    Mapped { file: MappedFile, offset: usize },
+}
+
+impl TableData {
+    fn bytes(&self) -> &[u8] {
+        match self {
+            TableData::Owned(data) => data,
+            TableData::Mapped { file, offset } => &file.bytes()[*offset..],
+        }
+    }
+}
+
+// A whole file mapped read only into memory
+// The standard library can't map files, so this asks the system for it directly
+// NOTE: Changing the file while it is mapped changes what the table reads, don't rebuild the
+// tables while a solver is using them
+#[cfg(all(unix, target_pointer_width = "64"))]
+struct MappedFile {
+    pointer: *mut std::ffi::c_void,
+    length: usize,
+}
+
+#[cfg(all(unix, target_pointer_width = "64"))]
+mod system {
+    use std::ffi::c_void;
+
+    // The same on every unix this builds for
+    pub(super) const PROT_READ: i32 = 1;
+    pub(super) const MAP_PRIVATE: i32 = 2;
+
+    extern "C" {
+        pub(super) fn mmap(
+            address: *mut c_void,
+            length: usize,
+            protection: i32,
+            flags: i32,
+            file: i32,
+            offset: i64,
+        ) -> *mut c_void;
+        pub(super) fn munmap(address: *mut c_void, length: usize) -> i32;
+    }
+}
+
+#[cfg(all(unix, target_pointer_width = "64"))]
+impl MappedFile {
+    fn new(file: &File) -> std::io::Result<Self> {
+        use std::os::fd::AsRawFd;
+        let length = file.metadata()?.len() as usize;
+        if length == 0 {
+            return Ok(MappedFile {
+                pointer: std::ptr::null_mut(),
+                length,
+            });
+        }
+        // SAFETY: The file is open for reading and the mapping is private and read only, so
+        // nothing this program does can write through it
+        let pointer = unsafe {
+            system::mmap(
+                std::ptr::null_mut(),
+                length,
+                system::PROT_READ,
+                system::MAP_PRIVATE,
+                file.as_raw_fd(),
+                0,
+            )
+        };
+        // mmap reports failure with an address of -1
+        if pointer as isize == -1 {
+            return Err(std::io::Error::last_os_error());
+        }
+        Ok(MappedFile { pointer, length })
+    }
+
+    fn bytes(&self) -> &[u8] {
+        if self.length == 0 {
+            return &[];
+        }
+        // SAFETY: The mapping covers length bytes and stays alive until this is dropped
+        unsafe { std::slice::from_raw_parts(self.pointer as *const u8, self.length) }
+    }
+}
+
+#[cfg(all(unix, target_pointer_width = "64"))]
+impl Drop for MappedFile {
+    fn drop(&mut self) {
+        if self.length > 0 {
+            // SAFETY: The pointer and length are exactly what mmap handed back
+            unsafe {
+                system::munmap(self.pointer, self.length);
+            }
+        }
+    }
+}
+
+// SAFETY: The mapping is only ever read, so sharing it between threads is the same as sharing
+// a slice
+#[cfg(all(unix, target_pointer_width = "64"))]
+unsafe impl Send for MappedFile {}
+#[cfg(all(unix, target_pointer_width = "64"))]
+unsafe impl Sync for MappedFile {}
+
+// Everywhere else the file is just read in
+#[cfg(not(all(unix, target_pointer_width = "64")))]
+struct MappedFile {
+    data: Vec<u8>,
+}
+
+#[cfg(not(all(unix, target_pointer_width = "64")))]
+impl MappedFile {
+    fn new(mut file: &File) -> std::io::Result<Self> {
+        use std::io::Seek;
+        let mut data = vec![];
+        file.rewind()?;
+        file.read_to_end(&mut data)?;
+        Ok(MappedFile { data })
+    }
+
+    fn bytes(&self) -> &[u8] {
+        &self.data
+    }
+}
@@ -0,0 +1,522 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use crate::geometry::{face_normal, face_point, in_layer, sticker_point, turn_point};
+use crate::net::ColorScheme;
+use crate::{read_2d_vec, CubeError, RubiksColor, RubiksCube};
+
+// Drawing a cube in perspective without a graphics card
+// Every sticker and the body behind it becomes a pair of triangles in 3D, which are shaded by
+// how squarely they face the light, projected through the camera and filled in pixel by pixel,
+// keeping whichever is nearest to the camera at each pixel
+
+// How far stickers stand out from the body, so they are always drawn in front of it
+const STICKER_LIFT: f64 = 0.005;
+
+// PNG data is stored uncompressed, in blocks of at most this many bytes
+const MAX_STORED_BLOCK: usize = 65535;
+
+// Where the picture is taken from
+// The cube sits at the origin reaching from -1 to 1 along every axis, with x to the right, y up
+// and z out of the front face
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct Camera {
+    pub(crate) eye: [f64; 3],
+    pub(crate) target: [f64; 3],
+    pub(crate) up: [f64; 3],
+    pub(crate) fov: f64, // The angle seen from the top of the picture to the bottom, in degrees
+}
+
+impl Camera {
+    // A camera looking at the middle of the cube from distance away, turned yaw degrees to the
+    // right of straight in front and raised pitch degrees above it
+    pub(crate) fn orbit(yaw: f64, pitch: f64, distance: f64) -> Self {
+        let (yaw, pitch) = (yaw.to_radians(), pitch.to_radians());
+        Camera {
+            eye: [
+                distance * pitch.cos() * yaw.sin(),
+                distance * pitch.sin(),
+                distance * pitch.cos() * yaw.cos(),
+            ],
+            target: [0.0; 3],
+            up: [0.0, 1.0, 0.0],
+            fov: 35.0,
+        }
+    }
+}
+
+impl Default for Camera {
+    // The usual view of the up, front and right faces
+    fn default() -> Self {
+        Camera::orbit(35.0, 30.0, 6.5)
+    }
+}
+
+// The widest or tallest picture that can be asked for
+// Pictures are drawn supersampled with a depth for every pixel, so one this size already takes a
+// couple of hundred megabytes while it's being drawn
+pub(crate) const MAX_IMAGE_SIZE: usize = 2048;
+
+#[derive(Clone, Debug)]
+pub(crate) struct RenderOptions {
+    pub(crate) width: usize,
+    pub(crate) height: usize,
+    pub(crate) camera: Camera,
+    pub(crate) scheme: ColorScheme,
+    pub(crate) body: [u8; 3],
+    pub(crate) background: [u8; 3],
+    pub(crate) gap: f64, // How much of each sticker's square is left as body around it
+    pub(crate) light: [f64; 3], // The direction the light comes from
+    pub(crate) ambient: f64, // How bright faces turned away from the light still are
+    pub(crate) supersample: usize, // Pixels are averaged from this many squared, smoothing edges
+}
+
+impl Default for RenderOptions {
+    fn default() -> Self {
+        RenderOptions {
+            width: 512,
+            height: 512,
+            camera: Camera::default(),
+            scheme: ColorScheme::default(),
+            body: [24, 24, 24],
+            background: [255, 255, 255],
+            gap: 0.1,
+            light: [0.4, 1.0, 0.7],
+            ambient: 0.45,
+            supersample: 2,
+        }
+    }
+}
+
+// A flat colored quad in 3D, with its corners going around the edge
+struct Quad {
+    corners: [[f64; 3]; 4],
+    normal: [f64; 3],
+    color: [u8; 3],
+}
+
+// An image as rows of red, green and blue pixels, top row first
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct Image {
+    width: usize,
+    height: usize,
+    pixels: Vec<[u8; 3]>,
+}
+
+// A layer caught part way through a turn, for drawing moves as they happen
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct PartialTurn {
+    pub(crate) face: RubiksColor,
+    pub(crate) layer: usize,
+    // How far the layer has turned clockwise as its face is looked at, in quarter turns, and
+    // negative for counterclockwise
+    pub(crate) quarter_turns: f64,
+}
+
+impl PartialTurn {
+    // Whether a point, in the doubled units of face_point, is in the turning layer
+    fn contains(&self, point: [i64; 3], face_size: usize) -> bool {
+        in_layer(point, self.face, self.layer, face_size)
+    }
+
+    // Turns a point around the face's axis by how far the layer has turned
+    fn rotate(&self, point: [f64; 3]) -> [f64; 3] {
+        turn_point(point, self.face, self.quarter_turns)
+    }
+}
+
+impl RubiksCube {
+    pub(crate) fn render(&self, options: &RenderOptions) -> Image {
+        self.render_turning(options, None)
+    }
+
+    // Renders the cube with a layer part way through turning
+    pub(crate) fn render_turning(
+        &self,
+        options: &RenderOptions,
+        turn: Option<PartialTurn>,
+    ) -> Image {
+        let scale = options.supersample.max(1);
+        let (width, height) = (options.width * scale, options.height * scale);
+        let mut image = Image::new(width, height, options.background);
+        // How near the nearest thing drawn at each pixel is, as one over its distance
+        let mut nearness = vec![0.0; width * height];
+        for (projected, color) in self.projected_quads(options, turn, width, height) {
+            for triangle in [[0, 1, 2], [0, 2, 3]] {
+                let points = triangle.map(|i| projected[i]);
+                fill_triangle(&mut image, &mut nearness, points, color);
+            }
+        }
+        image.shrink(scale)
+    }
+
+    // Every quad the camera can see, shaded, with its corners projected into a picture width by
+    // height pixels as pixel positions and nearness
+    pub(crate) fn projected_quads(
+        &self,
+        options: &RenderOptions,
+        turn: Option<PartialTurn>,
+        width: usize,
+        height: usize,
+    ) -> Vec<([[f64; 3]; 4], [u8; 3])> {
+        let camera = &options.camera;
+        let forward = normalize(sub(camera.target, camera.eye));
+        let right = normalize(cross(forward, camera.up));
+        let up = cross(right, forward);
+        let focal = height as f64 / 2.0 / (camera.fov.to_radians() / 2.0).tan();
+        let light = normalize(options.light);
+        let mut projected = vec![];
+        for quad in self.quads(options, turn) {
+            // Faces turned away from the camera are hidden behind the rest of the cube, and
+            // anything behind the camera can't be seen at all
+            if dot(quad.normal, sub(camera.eye, quad.corners[0])) <= 0.0
+                || quad
+                    .corners
+                    .iter()
+                    .any(|corner| dot(sub(*corner, camera.eye), forward) <= 0.0)
+            {
+                continue;
+            }
+            let brightness =
+                options.ambient + (1.0 - options.ambient) * dot(quad.normal, light).max(0.0);
+            let color = quad
+                .color
+                .map(|c| (f64::from(c) * brightness).round().min(255.0) as u8);
+            let corners = quad.corners.map(|corner| {
+                let offset = sub(corner, camera.eye);
+                let depth = dot(offset, forward);
+                [
+                    width as f64 / 2.0 + dot(offset, right) / depth * focal,
+                    height as f64 / 2.0 - dot(offset, up) / depth * focal,
+                    1.0 / depth,
+                ]
+            });
+            projected.push((corners, color));
+        }
+        projected
+    }
+
+    // The body behind every sticker and the sticker itself, with the turning layer turned
+    fn quads(&self, options: &RenderOptions, turn: Option<PartialTurn>) -> Vec<Quad> {
+        let n = self.face_size;
+        let mut quads = vec![];
+        for face in RubiksColor::ALL {
+            let normal = face_normal(face);
+            // Positions go from 0 to n across and down the face like stored stickers, lifted
+            // off the face by lift
+            let point = |u: f64, v: f64, lift: f64| {
+                let a = (2.0 * u) as i64 - n as i64;
+                let b = n as i64 - (2.0 * v) as i64;
+                let p = face_point(face, a, b, n).map(|c| c as f64 / n as f64);
+                [0, 1, 2].map(|i| p[i] + normal[i] * lift)
+            };
+            for y in 0..n {
+                for x in 0..n {
+                    let Some(color) = read_2d_vec(self.face(face), &n, &x, &y) else {
+                        continue;
+                    };
+                    let (left, top) = (x as f64, y as f64);
+                    let cell = |lift| {
+                        [
+                            point(left, top, lift),
+                            point(left + 1.0, top, lift),
+                            point(left + 1.0, top + 1.0, lift),
+                            point(left, top + 1.0, lift),
+                        ]
+                    };
+                    let corners = cell(STICKER_LIFT);
+                    // Pulling the corners in towards the middle leaves the gap around the sticker
+                    let middle = [0, 1, 2].map(|i| corners.iter().map(|c| c[i]).sum::<f64>() / 4.0);
+                    let mut body = Quad {
+                        corners: cell(0.0),
+                        normal,
+                        color: options.body,
+                    };
+                    let mut sticker = Quad {
+                        corners: corners.map(|corner| {
+                            [0, 1, 2]
+                                .map(|i| middle[i] + (corner[i] - middle[i]) * (1.0 - options.gap))
+                        }),
+                        normal,
+                        color: options.scheme.rgb(*color),
+                    };
+                    let center = sticker_point(face, x, y, n);
+                    if let Some(turn) = turn.filter(|turn| turn.contains(center, n)) {
+                        for quad in [&mut body, &mut sticker] {
+                            quad.corners = quad.corners.map(|corner| turn.rotate(corner));
+                            quad.normal = turn.rotate(quad.normal);
+                        }
+                    }
+                    quads.push(body);
+                    quads.push(sticker);
+                }
+            }
+        }
+        if let Some(turn) = turn.filter(|turn| turn.quarter_turns != 0.0) {
+            quads.extend(cut_quads(turn, n, options.body));
+        }
+        quads
+    }
+}
+
+// The inside of the cube showing where a turning layer has come away from the rest, as a square
+// of body on each side of each cut
+// Cuts are at the two flat sides of the layer, leaving out any that are a face of the cube
+fn cut_quads(turn: PartialTurn, face_size: usize, body: [u8; 3]) -> Vec<Quad> {
+    let n = face_size as i64;
+    let axis = face_normal(turn.face);
+    let outside = n - 2 * turn.layer as i64;
+    let mut quads = vec![];
+    // How deep each cut is along the axis, and which way the turning layer's side of it faces
+    for (depth, layer_side) in [(outside, 1.0), (outside - 2, -1.0)] {
+        if depth.abs() == n {
+            continue;
+        }
+        let corners = [(-n, -n), (n, -n), (n, n), (-n, n)].map(|(a, b)| {
+            let p = face_point(turn.face, a, b, face_size);
+            [0, 1, 2].map(|i| (p[i] as f64 - axis[i] * (n - depth) as f64) / n as f64)
+        });
+        let normal = axis.map(|c| c * layer_side);
+        quads.push(Quad {
+            corners: corners.map(|corner| turn.rotate(corner)),
+            normal: turn.rotate(normal),
+            color: body,
+        });
+        quads.push(Quad {
+            corners,
+            normal: normal.map(|c| -c),
+            color: body,
+        });
+    }
+    quads
+}
+
+// Fills in every pixel whose middle is inside the triangle and nearer than what's there already
+// Nearness is one over the distance, which unlike the distance changes evenly across the picture
+fn fill_triangle(image: &mut Image, nearness: &mut [f64], points: [[f64; 3]; 3], color: [u8; 3]) {
+    let [p0, p1, p2] = points;
+    let edge = |a: [f64; 3], b: [f64; 3], x: f64, y: f64| {
+        (b[0] - a[0]) * (y - a[1]) - (b[1] - a[1]) * (x - a[0])
+    };
+    let area = edge(p0, p1, p2[0], p2[1]);
+    if area.abs() < f64::EPSILON {
+        return;
+    }
+    let bound = |i: usize, limit: usize| {
+        let low = points
+            .iter()
+            .map(|p| p[i])
+            .fold(f64::MAX, f64::min)
+            .floor()
+            .max(0.0) as usize;
+        let high = points
+            .iter()
+            .map(|p| p[i])
+            .fold(f64::MIN, f64::max)
+            .ceil()
+            .max(0.0) as usize;
+        (low, high.min(limit))
+    };
+    let (left, right) = bound(0, image.width);
+    let (top, bottom) = bound(1, image.height);
+    for y in top..bottom {
+        for x in left..right {
+            let (px, py) = (x as f64 + 0.5, y as f64 + 0.5);
+            // How much of each corner is in this point, all positive only inside the triangle
+            let weights = [
+                edge(p1, p2, px, py) / area,
+                edge(p2, p0, px, py) / area,
+                edge(p0, p1, px, py) / area,
+            ];
+            if weights.iter().any(|w| *w < 0.0) {
+                continue;
+            }
+            let near = weights[0] * p0[2] + weights[1] * p1[2] + weights[2] * p2[2];
+            let index = y * image.width + x;
+            if near > nearness[index] {
+                nearness[index] = near;
+                image.pixels[index] = color;
+            }
+        }
+    }
+}
+
+impl Image {
+    pub(crate) fn new(width: usize, height: usize, color: [u8; 3]) -> Self {
+        Image {
+            width,
+            height,
+            pixels: vec![color; width * height],
+        }
+    }
+
+    pub(crate) fn width(&self) -> usize {
+        self.width
+    }
+
+    pub(crate) fn height(&self) -> usize {
+        self.height
+    }
+
+    pub(crate) fn pixel(&self, x: usize, y: usize) -> Option<[u8; 3]> {
+        if x >= self.width || y >= self.height {
+            return None;
+        }
+        Some(self.pixels[y * self.width + x])
+    }
+
+    // Colors one pixel, doing nothing if it is outside the image
+    pub(crate) fn set_pixel(&mut self, x: usize, y: usize, color: [u8; 3]) {
+        if x < self.width && y < self.height {
+            self.pixels[y * self.width + x] = color;
+        }
+    }
+
+    // Averages every factor by factor block of pixels into one
+    fn shrink(self, factor: usize) -> Image {
+        if factor <= 1 {
+            return self;
+        }
+        let (width, height) = (self.width / factor, self.height / factor);
+        let mut pixels = Vec::with_capacity(width * height);
+        for y in 0..height {
+            for x in 0..width {
+                let mut total = [0u32; 3];
+                for dy in 0..factor {
+                    for dx in 0..factor {
+                        let pixel = self.pixels[(y * factor + dy) * self.width + x * factor + dx];
+                        for i in 0..3 {
+                            total[i] += u32::from(pixel[i]);
+                        }
+                    }
+                }
+                let count = (factor * factor) as u32;
+                pixels.push(total.map(|t| ((t + count / 2) / count) as u8));
+            }
+        }
+        Image {
+            width,
+            height,
+            pixels,
+        }
+    }
+
+    // Saves as a PNG or PPM file, going by the file's extension
+    pub(crate) fn save(&self, path: impl AsRef<Path>) -> Result<(), CubeError> {
+        let path = path.as_ref();
+        let extension = path.extension().and_then(|e| e.to_str()).unwrap_or("");
+        let mut out = BufWriter::new(File::create(path)?);
+        match extension.to_ascii_lowercase().as_str() {
+            "png" => self.write_png(&mut out)?,
+            "ppm" => self.write_ppm(&mut out)?,
+            _ => {
+                return Err(CubeError::Parse(format!(
+                    "\"{}\" as an image file name, it should end in .png or .ppm",
+                    path.display()
+                )))
+            }
+        }
+        out.flush()?;
+        Ok(())
+    }
+
+    // The binary PPM format, a short text header and then the pixels as they are
+    pub(crate) fn write_ppm(&self, out: &mut impl Write) -> std::io::Result<()> {
+        write!(out, "P6\n{} {}\n255\n", self.width, self.height)?;
+        for pixel in &self.pixels {
+            out.write_all(pixel)?;
+        }
+        Ok(())
+    }
+
+    // A PNG with the pixel data stored without compression, which every PNG reader understands
+    // and needs nothing beyond a couple of checksums to write
+    pub(crate) fn write_png(&self, out: &mut impl Write) -> std::io::Result<()> {
+        out.write_all(b"\x89PNG\r\n\x1a\n")?;
+        // Step 1: The header, 8 bits per channel of red, green and blue, no interlacing
+        let mut header = vec![];
+        header.extend((self.width as u32).to_be_bytes());
+        header.extend((self.height as u32).to_be_bytes());
+        header.extend([8, 2, 0, 0, 0]);
+        write_chunk(out, b"IHDR", &header)?;
+        // Step 2: Every row starts with a byte saying it isn't filtered
+        let mut raw = Vec::with_capacity(self.height * (1 + 3 * self.width));
+        for row in self.pixels.chunks(self.width.max(1)) {
+            raw.push(0);
+            raw.extend(row.iter().flatten());
+        }
+        // Step 3: Wrap it in a zlib stream made of stored deflate blocks
+        let mut data = vec![0x78, 0x01];
+        let blocks = raw.chunks(MAX_STORED_BLOCK).collect::<Vec<_>>();
+        for (i, block) in blocks.iter().enumerate() {
+            let last = i + 1 == blocks.len();
+            data.push(u8::from(last));
+            let length = block.len() as u16;
+            data.extend(length.to_le_bytes());
+            data.extend((!length).to_le_bytes());
+            data.extend(*block);
+        }
+        if blocks.is_empty() {
+            data.extend([1, 0, 0, 0xFF, 0xFF]);
+        }
+        data.extend(adler32(&raw).to_be_bytes());
+        write_chunk(out, b"IDAT", &data)?;
+        write_chunk(out, b"IEND", &[])
+    }
+}
+
+fn write_chunk(out: &mut impl Write, kind: &[u8; 4], data: &[u8]) -> std::io::Result<()> {
+    out.write_all(&(data.len() as u32).to_be_bytes())?;
+    out.write_all(kind)?;
+    out.write_all(data)?;
+    let crc = crc32(kind.iter().chain(data));
+    out.write_all(&crc.to_be_bytes())
+}
+
+// The checksum PNG chunks end with
+pub(crate) fn crc32<'a>(bytes: impl Iterator<Item = &'a u8>) -> u32 {
+    let mut crc = !0u32;
+    for byte in bytes {
+        crc ^= u32::from(*byte);
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ 0xEDB8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+// The checksum zlib streams end with
+fn adler32(bytes: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for byte in bytes {
+        a = (a + u32::from(*byte)) % 65521;
+        b = (b + a) % 65521;
+    }
+    b << 16 | a
+}
+
+fn sub(a: [f64; 3], b: [f64; 3]) -> [f64; 3] {
+    [a[0] - b[0], a[1] - b[1], a[2] - b[2]]
+}
+
+fn dot(a: [f64; 3], b: [f64; 3]) -> f64 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+fn cross(a: [f64; 3], b: [f64; 3]) -> [f64; 3] {
+    [
+        a[1] * b[2] - a[2] * b[1],
+        a[2] * b[0] - a[0] * b[2],
+        a[0] * b[1] - a[1] * b[0],
+    ]
+}
+
+fn normalize(a: [f64; 3]) -> [f64; 3] {
+    let length = dot(a, a).sqrt();
+    a.map(|c| c / length)
+}
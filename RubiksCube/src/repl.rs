@@ -0,0 +1,232 @@
+use std::io::{BufRead, Write};
+
+use crate::net::{ColorMode, ColorScheme};
+use crate::notation::{Algorithm, Move};
+use crate::scramble::Scrambler;
+use crate::solver::{self, Solver};
+use crate::{CubeError, RubiksCube};
+
+// Playing with a cube from the terminal, a line at a time
+// Each line is either moves in the usual notation or one of the commands in HELP, and the cube is
+// drawn again after anything that changes it
+
+const HELP: &str = "\
+moves like R U R' U'  turn the cube
+reset                 go back to a solved cube
+size N                start again with an NxN cube
+scramble              start again from a random scramble
+solve                 find and make moves that solve a 3x3
+undo [N]              take back the last move, or the last N
+redo [N]              make undone moves again
+show                  draw the cube
+show facelets         print the cube as a facelet string
+history               print the scramble and every move since
+help                  print this
+quit                  leave";
+
+// What a line asks to be shown after it runs
+enum Reply {
+    Cube(Option<String>), // The cube, after a message if there is one
+    Text(String),
+    Quit,
+}
+
+pub(crate) struct Repl {
+    cube: RubiksCube,
+    scramble: Option<Algorithm>, // What the cube started from, if it wasn't solved
+    moves: Vec<Move>,            // Everything done since, oldest first
+    undone: Vec<Move>,           // Moves taken back, the most recent last, until a new move
+    scrambler: Scrambler,
+    solver: Option<Solver>, // Only loaded the first time it's needed, since the tables are big
+    scheme: ColorScheme,
+    colors: ColorMode,
+}
+
+impl Repl {
+    pub(crate) fn new(face_size: usize) -> Result<Self, CubeError> {
+        Ok(Repl {
+            cube: RubiksCube::try_new(face_size)?,
+            scramble: None,
+            moves: vec![],
+            undone: vec![],
+            scrambler: Scrambler::new(),
+            solver: None,
+            scheme: ColorScheme::default(),
+            colors: ColorMode::detect(),
+        })
+    }
+
+    // Solves with this solver instead of loading one the first time it's needed
+    pub(crate) fn with_solver(mut self, solver: Solver) -> Self {
+        self.solver = Some(solver);
+        self
+    }
+
+    // Reads lines until the input ends or asks to quit, writing everything to out
+    // Mistakes in a line are reported and the line ignored, only failing to read or write stops it
+    pub(crate) fn run(
+        &mut self,
+        input: impl BufRead,
+        out: &mut impl Write,
+    ) -> Result<(), CubeError> {
+        writeln!(out, "{}", self.net())?;
+        write!(out, "> ")?;
+        out.flush()?;
+        for line in input.lines() {
+            match self.run_line(&line?) {
+                Ok(Reply::Cube(message)) => {
+                    if let Some(message) = message {
+                        writeln!(out, "{message}")?;
+                    }
+                    writeln!(out, "{}", self.net())?;
+                }
+                Ok(Reply::Text(text)) => writeln!(out, "{text}")?,
+                Ok(Reply::Quit) => return Ok(()),
+                Err(error) => writeln!(out, "error: {error}")?,
+            }
+            write!(out, "> ")?;
+            out.flush()?;
+        }
+        writeln!(out)?;
+        Ok(())
+    }
+
+    fn run_line(&mut self, line: &str) -> Result<Reply, CubeError> {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let count = |words: &[&str]| match words {
+            [] => Ok(1),
+            [count] => count
+                .parse()
+                .map_err(|_| CubeError::Parse(format!("\"{count}\" as a number of moves"))),
+            _ => Err(CubeError::Parse(format!("\"{line}\" as a command"))),
+        };
+        match words.as_slice() {
+            [] | ["show"] => Ok(Reply::Cube(None)),
+            ["show", "facelets"] => Ok(Reply::Text(self.cube.to_facelets())),
+            ["help"] => Ok(Reply::Text(HELP.to_string())),
+            ["quit"] | ["exit"] => Ok(Reply::Quit),
+            ["history"] => Ok(Reply::Text(self.history())),
+            ["reset"] => {
+                self.start(RubiksCube::try_new(self.cube.face_size)?, None);
+                Ok(Reply::Cube(None))
+            }
+            ["size", size] => {
+                let size = size
+                    .parse()
+                    .map_err(|_| CubeError::Parse(format!("\"{size}\" as a cube size")))?;
+                self.start(RubiksCube::try_new(size)?, None);
+                Ok(Reply::Cube(None))
+            }
+            ["scramble"] => {
+                let scramble = self.scrambler.scramble(self.cube.face_size);
+                let cube = RubiksCube::try_new(self.cube.face_size)?.try_do_algorithm(&scramble)?;
+                let message = scramble.to_string();
+                self.start(cube, Some(scramble));
+                Ok(Reply::Cube(Some(message)))
+            }
+            ["solve"] => {
+                let solution = self.solve()?;
+                let message = if solution.moves.is_empty() {
+                    "already solved".to_string()
+                } else {
+                    solution.to_string()
+                };
+                self.play(&solution)?;
+                Ok(Reply::Cube(Some(message)))
+            }
+            ["undo", rest @ ..] => {
+                for _ in 0..count(rest)? {
+                    let Some(m) = self.moves.pop() else {
+                        break;
+                    };
+                    self.cube = self.cube.try_do_algorithm(&Algorithm {
+                        moves: vec![m.inverse()],
+                    })?;
+                    self.undone.push(m);
+                }
+                Ok(Reply::Cube(None))
+            }
+            ["redo", rest @ ..] => {
+                for _ in 0..count(rest)? {
+                    let Some(m) = self.undone.pop() else {
+                        break;
+                    };
+                    self.cube = self.cube.try_do_algorithm(&Algorithm { moves: vec![m] })?;
+                    self.moves.push(m);
+                }
+                Ok(Reply::Cube(None))
+            }
+            _ => {
+                self.play(&line.parse()?)?;
+                Ok(Reply::Cube(None))
+            }
+        }
+    }
+
+    // Makes the moves, or none of them if any don't fit the cube
+    fn play(&mut self, algorithm: &Algorithm) -> Result<(), CubeError> {
+        self.cube = self.cube.try_do_algorithm(algorithm)?;
+        self.moves.extend(&algorithm.moves);
+        if !algorithm.moves.is_empty() {
+            self.undone.clear();
+        }
+        Ok(())
+    }
+
+    // Starts again from a new cube, forgetting every move
+    fn start(&mut self, cube: RubiksCube, scramble: Option<Algorithm>) {
+        self.cube = cube;
+        self.scramble = scramble;
+        self.moves.clear();
+        self.undone.clear();
+    }
+
+    fn solve(&mut self) -> Result<Algorithm, CubeError> {
+        if self.cube.face_size != 3 {
+            return Err(CubeError::WrongSize {
+                face_size: self.cube.face_size,
+                expected: 3,
+            });
+        }
+        let solver = match self.solver.take() {
+            Some(solver) => solver,
+            None => {
+                if !std::path::Path::new(solver::DEFAULT_TABLE_DIR).exists() {
+                    eprintln!("building the solver's tables, `tables` saves them for next time");
+                }
+                Solver::load_or_new(solver::DEFAULT_TABLE_DIR)?
+            }
+        };
+        let solution = solver.solve(&self.cube);
+        self.solver = Some(solver);
+        // The solver goes by the pieces around the centers, so turned centers are left as they are
+        let solution = solution?;
+        if !self.cube.try_do_algorithm(&solution)?.is_solved() {
+            return Err(CubeError::NotFound(
+                "with the centers turned, turn the middle layers back first".to_string(),
+            ));
+        }
+        Ok(solution)
+    }
+
+    fn history(&self) -> String {
+        let scramble = self
+            .scramble
+            .as_ref()
+            .map_or("none".to_string(), Algorithm::to_string);
+        let moves = Algorithm {
+            moves: self.moves.clone(),
+        };
+        format!(
+            "scramble: {scramble}\nmoves ({}): {moves}",
+            self.moves.len()
+        )
+    }
+
+    fn net(&self) -> String {
+        self.cube
+            .color_net(self.scheme, self.colors)
+            .fit_terminal()
+            .to_string()
+    }
+}
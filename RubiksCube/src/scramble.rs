@@ -0,0 +1,103 @@
+use std::collections::hash_map::RandomState;
+use std::hash::BuildHasher;
+
+use crate::notation::{Algorithm, Move};
+use crate::{RubiksColor, TurnDirection};
+
+// Random scrambles, as a number of random moves that suits the size of the cube
+// Moves never undo or repeat the one before, and moves on the same axis only come in one order,
+// since turning layers that don't touch in either order gives the same cube
+// The middle layer of odd sized cubes is never turned, so the centers stay put and the cube can
+// still be solved by anything that expects white on top and green in front
+
+// Makes random numbers for scrambles
+// Each scrambler is seeded differently, from the same source std uses to seed its hash maps
+pub(crate) struct Scrambler {
+    state: u64,
+}
+
+impl Scrambler {
+    pub(crate) fn new() -> Self {
+        Self::with_seed(RandomState::new().hash_one(0u64))
+    }
+
+    // A scrambler that always makes the same scrambles, for repeatable runs
+    pub(crate) fn with_seed(seed: u64) -> Self {
+        Scrambler { state: seed }
+    }
+
+    // How many moves a scramble of a cube this size has, close to what competitions use
+    pub(crate) fn length(face_size: usize) -> usize {
+        match face_size {
+            0 => 0,
+            1 => 5,
+            2 => 11,
+            3 => 25,
+            n => 20 * (n - 2),
+        }
+    }
+
+    pub(crate) fn scramble(&mut self, face_size: usize) -> Algorithm {
+        let mut moves: Vec<Move> = vec![];
+        while moves.len() < Self::length(face_size) {
+            let face = RubiksColor::ALL[self.below(6)];
+            // Only the layers in the nearer half of each face are turned, since the opposite face
+            // turns the rest, and that leaves the middle of odd sized cubes where it is
+            let layer = self.below((face_size / 2).max(1));
+            // A quarter turn either way or a half turn, each as likely as the others
+            let (direction, double) = match self.below(3) {
+                0 => (TurnDirection::CLOCKWISE, false),
+                1 => (TurnDirection::COUNTERCLOCKWISE, false),
+                _ => (TurnDirection::CLOCKWISE, true),
+            };
+            let next = Move {
+                face,
+                layer,
+                direction,
+                double,
+            };
+            // Moves on the same axis as the one before have to be further along it
+            if let Some(last) = moves.last() {
+                if axis(last.face) == axis(face)
+                    && slice(&next, face_size) <= slice(last, face_size)
+                {
+                    continue;
+                }
+            }
+            moves.push(next);
+        }
+        Algorithm { moves }
+    }
+
+    // A random number from 0 up to but not including limit
+    fn below(&mut self, limit: usize) -> usize {
+        // splitmix64, which is quick and spreads even similar seeds out well
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^= z >> 31;
+        (z % limit.max(1) as u64) as usize
+    }
+}
+
+impl Default for Scrambler {
+    fn default() -> Self {
+        Scrambler::new()
+    }
+}
+
+// Opposite faces turn around the same axis
+fn axis(face: RubiksColor) -> usize {
+    face.index() / 2
+}
+
+// Which layer along its axis a move turns, counted from the first face of each opposite pair in
+// RubiksColor::ALL
+fn slice(m: &Move, face_size: usize) -> usize {
+    if m.face.index().is_multiple_of(2) {
+        m.layer
+    } else {
+        face_size - 1 - m.layer
+    }
+}
@@ -0,0 +1,589 @@
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::coordinates::{face_moves, phase_two_moves, Coordinate, CoordinateTable, CubieCube};
+use crate::notation::{Algorithm, Move};
+use crate::parallel;
+use crate::pattern_db::{MoveSet, PatternDatabase};
+use crate::{CubeError, RubiksCube};
+
+// A two phase solver for the 3x3
+// Phase one gets the cube into the group where every piece is oriented and the middle layer
+// edges are in the middle layer, and phase two finishes it off using only the moves that stay in
+// that group. Both phases are depth first searches that deepen one move at a time, cut short
+// whenever the pattern databases say the rest of the phase can't be done in the moves left
+
+// Where the tables command saves the pattern databases, and where solving looks for them
+pub(crate) const DEFAULT_TABLE_DIR: &str = "tables";
+
+// The most moves either phase is allowed, which every 3x3 can be done in
+const MAX_PHASE_ONE: usize = 12;
+const MAX_PHASE_TWO: usize = 18;
+
+// How many moves into phase one the search is split up to share between threads
+// Two moves makes a couple of hundred pieces, enough to keep plenty of threads busy
+const SPLIT_MOVES: usize = 2;
+
+// How many positions a thread looks at between checking the limits and adding to the count
+const CHECK_NODES: u64 = 4096;
+
+// How often progress is reported while a search is in the middle of a depth
+const REPORT_INTERVAL: Duration = Duration::from_millis(100);
+
+// The pattern databases the search prunes with, and the file each one is saved in
+const PRUNING_TABLES: [(&str, [Coordinate; 2], MoveSet); 4] = [
+    (
+        "twist_slice.pdb",
+        [Coordinate::CornerOrientation, Coordinate::UdSlice],
+        MoveSet::FaceMoves,
+    ),
+    (
+        "flip_slice.pdb",
+        [Coordinate::EdgeOrientation, Coordinate::UdSlice],
+        MoveSet::FaceMoves,
+    ),
+    (
+        "corners_slice.pdb",
+        [Coordinate::CornerPermutation, Coordinate::SlicePermutation],
+        MoveSet::PhaseTwo,
+    ),
+    (
+        "edges_slice.pdb",
+        [Coordinate::UdEdgePermutation, Coordinate::SlicePermutation],
+        MoveSet::PhaseTwo,
+    ),
+];
+
+// Builds every pattern database the solver uses and saves them in a directory
+// Prints each table as it goes since the whole thing takes a little while
+pub(crate) fn build_tables(dir: impl AsRef<Path>, threads: usize) -> Result<(), CubeError> {
+    std::fs::create_dir_all(&dir)?;
+    for (name, coordinates, move_set) in PRUNING_TABLES {
+        let start = std::time::Instant::now();
+        let database = PatternDatabase::build(&coordinates, move_set, threads)?;
+        let path = dir.as_ref().join(name);
+        database.save(&path)?;
+        println!(
+            "built {} in {:.1}s",
+            path.display(),
+            start.elapsed().as_secs_f64()
+        );
+    }
+    Ok(())
+}
+
+// Extra things a solve can be asked to do, all off by default
+#[derive(Clone, Debug, Default)]
+pub(crate) struct SolveOptions {
+    // Keep looking for shorter solutions until one is at most this long. Without it the first
+    // solution found is good enough, and 0 keeps improving until a limit runs out
+    pub(crate) max_length: Option<usize>,
+    pub(crate) time_limit: Option<Duration>,
+    pub(crate) node_limit: Option<u64>, // How many positions the search may look at
+    pub(crate) cancel: Option<CancelToken>,
+}
+
+// Lets another thread, a UI for instance, stop a solve early
+// Clones share the same flag, so keep one and put the other in the solve's options
+#[derive(Clone, Debug, Default)]
+pub(crate) struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    pub(crate) fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub(crate) fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+// How a solve is getting on, handed to its progress callback
+#[derive(Clone, Debug)]
+pub(crate) struct Progress {
+    pub(crate) depth: usize, // How many moves phase one is being tried with
+    pub(crate) nodes: u64,   // How many positions have been looked at
+    pub(crate) elapsed: Duration,
+    pub(crate) best: Option<Algorithm>, // The shortest solution so far
+}
+
+// Clones share the pattern databases, so a clone costs little more than the small move tables
+#[derive(Clone)]
+pub(crate) struct Solver {
+    moves: Vec<Move>,            // The face moves, in the order the phase one tables use
+    move_cubies: Vec<CubieCube>, // What each face move does to the pieces
+    phase_two_moves: Vec<usize>, // Which face move each phase two move is
+    phase_one: [CoordinateTable; 3],
+    phase_two: [CoordinateTable; 3],
+    pruning: Arc<[PatternDatabase; 4]>,
+    threads: usize, // How many threads a solve is spread over
+}
+
+impl Solver {
+    // Builds every table in memory, for when there are no saved ones
+    pub(crate) fn new() -> Result<Self, CubeError> {
+        let threads = parallel::default_threads();
+        let pruning = PRUNING_TABLES
+            .iter()
+            .map(|(_, coordinates, move_set)| {
+                PatternDatabase::build(coordinates, *move_set, threads)
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Self::with_databases(pruning)
+    }
+
+    // Maps the pattern databases saved by build_tables
+    pub(crate) fn load(dir: impl AsRef<Path>) -> Result<Self, CubeError> {
+        let mut pruning = vec![];
+        for (name, coordinates, move_set) in PRUNING_TABLES {
+            let database = PatternDatabase::load(dir.as_ref().join(name))?;
+            if database.coordinates() != coordinates || database.move_set() != move_set {
+                return Err(CubeError::Parse(format!(
+                    "{name}: pattern database is for different coordinates"
+                )));
+            }
+            pruning.push(database);
+        }
+        Self::with_databases(pruning)
+    }
+
+    // Loads the saved tables if there are any, and builds them otherwise
+    pub(crate) fn load_or_new(dir: impl AsRef<Path>) -> Result<Self, CubeError> {
+        if dir.as_ref().exists() {
+            Self::load(dir)
+        } else {
+            Self::new()
+        }
+    }
+
+    fn with_databases(pruning: Vec<PatternDatabase>) -> Result<Self, CubeError> {
+        let moves = face_moves();
+        let move_cubies = moves
+            .iter()
+            .map(CubieCube::of_move)
+            .collect::<Result<Vec<_>, _>>()?;
+        let phase_two_moves: Vec<usize> = phase_two_moves()
+            .iter()
+            .map(|m| moves.iter().position(|face_move| face_move == m).unwrap())
+            .collect();
+        let phase_one = [
+            Coordinate::CornerOrientation,
+            Coordinate::EdgeOrientation,
+            Coordinate::UdSlice,
+        ]
+        .map(CoordinateTable::new);
+        let phase_two = [
+            Coordinate::CornerPermutation,
+            Coordinate::UdEdgePermutation,
+            Coordinate::SlicePermutation,
+        ]
+        .map(|coordinate| CoordinateTable::with_moves(coordinate, MoveSet::PhaseTwo.moves()));
+        let [a, b, c] = phase_one;
+        let [d, e, f] = phase_two;
+        Ok(Solver {
+            moves,
+            move_cubies,
+            phase_two_moves,
+            phase_one: [a?, b?, c?],
+            phase_two: [d?, e?, f?],
+            pruning: Arc::new(
+                pruning.try_into().map_err(|_| {
+                    CubeError::Parse("wrong number of pattern databases".to_string())
+                })?,
+            ),
+            threads: parallel::default_threads(),
+        })
+    }
+
+    // Uses this many threads for each solve instead of one per core
+    // The solution found is the same however many threads look for it
+    pub(crate) fn with_threads(mut self, threads: usize) -> Self {
+        self.threads = threads.max(1);
+        self
+    }
+
+    // Finds a sequence of moves that solves a 3x3
+    // The first solution found is returned, so it won't usually be the shortest one
+    pub(crate) fn solve(&self, cube: &RubiksCube) -> Result<Algorithm, CubeError> {
+        self.solve_with(cube, &SolveOptions::default(), &|_| {})
+    }
+
+    // Solves a 3x3, calling report whenever the search goes a move deeper, finds a shorter
+    // solution, or has been busy for a while. It may be called from any of the search's threads
+    // When a limit runs out or the solve is cancelled the shortest solution so far is returned,
+    // and it's only an error if there isn't one yet
+    pub(crate) fn solve_with(
+        &self,
+        cube: &RubiksCube,
+        options: &SolveOptions,
+        report: &(dyn Fn(&Progress) + Sync),
+    ) -> Result<Algorithm, CubeError> {
+        let start = CubieCube::from_cube(cube)?;
+        check_solvable(&start)?;
+        let values = self
+            .phase_one
+            .each_ref()
+            .map(|t| t.coordinate().get(&start));
+        let shared = Shared {
+            options,
+            report,
+            stop_at: options.max_length.unwrap_or(usize::MAX),
+            started: Instant::now(),
+            last_report: Mutex::new(Instant::now()),
+            depth: AtomicUsize::new(0),
+            nodes: AtomicU64::new(0),
+            stopped: AtomicBool::new(false),
+            best: Mutex::new(None),
+            best_length: AtomicUsize::new(usize::MAX),
+        };
+        for depth in 0..=MAX_PHASE_ONE {
+            // Every solution from here on takes at least depth moves
+            if shared.stopped.load(Ordering::Relaxed)
+                || shared.best_length.load(Ordering::Relaxed) <= depth
+            {
+                break;
+            }
+            shared.depth.store(depth, Ordering::Relaxed);
+            shared.report();
+            // Each thread takes the paths starting with a few moves at a time, and the answer from
+            // the earliest starting moves wins, just like searching them one after another
+            let mut starts = vec![];
+            self.phase_one_starts(
+                &mut vec![],
+                values,
+                depth,
+                SPLIT_MOVES.min(depth),
+                &mut starts,
+            );
+            let solution =
+                parallel::first_in_order(self.threads, &starts, |(path, values), cancelled| {
+                    let mut search = Search {
+                        start,
+                        path: path.clone(),
+                        cancelled,
+                        shared: &shared,
+                        nodes: 0,
+                    };
+                    let found = self.phase_one_search(&mut search, *values, depth - path.len());
+                    shared.add_nodes(search.nodes);
+                    found.then(|| self.algorithm(&search.path))
+                });
+            if let Some(solution) = solution {
+                return Ok(solution);
+            }
+        }
+        shared.report();
+        let stopped = shared.stopped.load(Ordering::Relaxed);
+        shared.best.into_inner().unwrap().ok_or_else(|| {
+            CubeError::NotFound(
+                if options
+                    .cancel
+                    .as_ref()
+                    .is_some_and(CancelToken::is_cancelled)
+                {
+                    "before the solve was cancelled".to_string()
+                } else if stopped {
+                    "within the time or node limit".to_string()
+                } else {
+                    "within the move limit".to_string()
+                },
+            )
+        })
+    }
+
+    fn algorithm(&self, path: &[usize]) -> Algorithm {
+        Algorithm {
+            moves: path.iter().map(|m| self.moves[*m]).collect(),
+        }
+    }
+
+    fn phase_one_bound(&self, [twist, flip, slice]: [usize; 3]) -> usize {
+        let twist_slice = self.pruning[0].distance(&[twist, slice]);
+        let flip_slice = self.pruning[1].distance(&[flip, slice]);
+        twist_slice.max(flip_slice) as usize
+    }
+
+    fn phase_two_bound(&self, [corners, edges, slice]: [usize; 3]) -> usize {
+        let corners_slice = self.pruning[2].distance(&[corners, slice]);
+        let edges_slice = self.pruning[3].distance(&[edges, slice]);
+        corners_slice.max(edges_slice) as usize
+    }
+
+    // Lists the first few moves of every path phase_one_search would try, in the order it would
+    // try them, along with the coordinates they lead to
+    fn phase_one_starts(
+        &self,
+        path: &mut Vec<usize>,
+        values: [usize; 3],
+        depth: usize,
+        moves: usize,
+        starts: &mut Vec<(Vec<usize>, [usize; 3])>,
+    ) {
+        if moves == 0 {
+            starts.push((path.clone(), values));
+            return;
+        }
+        // Cut off exactly where phase_one_search would
+        if self.phase_one_bound(values) > depth {
+            return;
+        }
+        for m in 0..self.moves.len() {
+            if !follows(path.last(), m) {
+                continue;
+            }
+            let next = [0, 1, 2].map(|i| self.phase_one[i].apply(values[i], m));
+            path.push(m);
+            self.phase_one_starts(path, next, depth - 1, moves - 1, starts);
+            path.pop();
+        }
+    }
+
+    // Looks for ways into the phase two group in exactly depth moves, trying phase two from each
+    fn phase_one_search(&self, search: &mut Search, values: [usize; 3], depth: usize) -> bool {
+        if !search.visit() {
+            return false;
+        }
+        let bound = self.phase_one_bound(values);
+        if depth == 0 {
+            // Ending on a phase two move means a shorter phase one was already tried
+            let ends_in_phase_two = search
+                .path
+                .last()
+                .is_some_and(|m| self.phase_two_moves.contains(m));
+            return bound == 0 && !ends_in_phase_two && self.start_phase_two(search);
+        }
+        // Phase one carries on through the group, since leaving it and coming back can make the
+        // whole solution shorter than finishing with phase two moves from here
+        if bound > depth {
+            return false;
+        }
+        for m in 0..self.moves.len() {
+            if !follows(search.path.last(), m) {
+                continue;
+            }
+            let next = [0, 1, 2].map(|i| self.phase_one[i].apply(values[i], m));
+            search.path.push(m);
+            if self.phase_one_search(search, next, depth - 1) {
+                return true;
+            }
+            search.path.pop();
+        }
+        false
+    }
+
+    // Finishes off the cube from the end of a phase one path, keeping the solution if it's the
+    // shortest so far, and says whether it's short enough to stop at
+    fn start_phase_two(&self, search: &mut Search) -> bool {
+        let cubies = search
+            .path
+            .iter()
+            .fold(search.start, |cubies, m| cubies.then(&self.move_cubies[*m]));
+        let values = self
+            .phase_two
+            .each_ref()
+            .map(|t| t.coordinate().get(&cubies));
+        let phase_one_length = search.path.len();
+        let Some(most) = search.shared.length_limit().checked_sub(phase_one_length) else {
+            return false;
+        };
+        for depth in self.phase_two_bound(values)..=MAX_PHASE_TWO.min(most) {
+            if self.phase_two_search(search, values, depth) {
+                // Phase two is tried shortest first, so this is the best this path can do
+                search.shared.record(self.algorithm(&search.path));
+                if search.path.len() <= search.shared.stop_at {
+                    return true;
+                }
+                search.path.truncate(phase_one_length);
+                return false;
+            }
+            search.path.truncate(phase_one_length);
+        }
+        false
+    }
+
+    fn phase_two_search(&self, search: &mut Search, values: [usize; 3], depth: usize) -> bool {
+        if !search.visit() {
+            return false;
+        }
+        if depth == 0 {
+            return values == [0; 3];
+        }
+        if self.phase_two_bound(values) > depth {
+            return false;
+        }
+        for (i, m) in self.phase_two_moves.iter().enumerate() {
+            if !follows(search.path.last(), *m) {
+                continue;
+            }
+            let next = [0, 1, 2].map(|k| self.phase_two[k].apply(values[k], i));
+            search.path.push(*m);
+            if self.phase_two_search(search, next, depth - 1) {
+                return true;
+            }
+            search.path.pop();
+        }
+        false
+    }
+}
+
+// What every thread working on the same solve shares
+struct Shared<'a> {
+    options: &'a SolveOptions,
+    report: &'a (dyn Fn(&Progress) + Sync),
+    stop_at: usize, // Any solution this short ends the search
+    started: Instant,
+    last_report: Mutex<Instant>,
+    depth: AtomicUsize,
+    nodes: AtomicU64,
+    stopped: AtomicBool, // Set once a limit runs out or the solve is cancelled
+    best: Mutex<Option<Algorithm>>,
+    best_length: AtomicUsize,
+}
+
+impl Shared<'_> {
+    fn report(&self) {
+        *self.last_report.lock().unwrap() = Instant::now();
+        let progress = Progress {
+            depth: self.depth.load(Ordering::Relaxed),
+            nodes: self.nodes.load(Ordering::Relaxed),
+            elapsed: self.started.elapsed(),
+            best: self.best.lock().unwrap().clone(),
+        };
+        (self.report)(&progress);
+    }
+
+    // Counts positions a thread has looked at, and checks whether it's time to stop or to let
+    // the caller know how things are going
+    fn add_nodes(&self, count: u64) {
+        let nodes = self.nodes.fetch_add(count, Ordering::Relaxed) + count;
+        let options = self.options;
+        if options.node_limit.is_some_and(|limit| nodes >= limit)
+            || options
+                .time_limit
+                .is_some_and(|limit| self.started.elapsed() >= limit)
+            || options
+                .cancel
+                .as_ref()
+                .is_some_and(CancelToken::is_cancelled)
+        {
+            self.stopped.store(true, Ordering::Relaxed);
+        }
+        let due = self
+            .last_report
+            .try_lock()
+            .is_ok_and(|last| last.elapsed() >= REPORT_INTERVAL);
+        if due {
+            self.report();
+        }
+    }
+
+    // The longest solution still worth finding
+    // Anything short enough to stop at is, and otherwise it has to beat the best so far
+    fn length_limit(&self) -> usize {
+        let best = self.best_length.load(Ordering::Relaxed);
+        if best > self.stop_at {
+            best - 1
+        } else {
+            usize::MAX
+        }
+    }
+
+    fn record(&self, solution: Algorithm) {
+        let mut best = self.best.lock().unwrap();
+        if best
+            .as_ref()
+            .is_none_or(|best| solution.moves.len() < best.moves.len())
+        {
+            self.best_length
+                .store(solution.moves.len(), Ordering::Relaxed);
+            *best = Some(solution);
+            drop(best);
+            self.report();
+        }
+    }
+}
+
+// The state of one thread's part of a search
+struct Search<'a> {
+    start: CubieCube,
+    path: Vec<usize>,                // The face moves made so far
+    cancelled: &'a dyn Fn() -> bool, // Whether another thread has made this search pointless
+    shared: &'a Shared<'a>,
+    nodes: u64, // Positions looked at that haven't been added to the shared count yet
+}
+
+impl Search<'_> {
+    // Counts another position, and says whether the search should keep going
+    fn visit(&mut self) -> bool {
+        self.nodes += 1;
+        if self.nodes == CHECK_NODES {
+            self.shared.add_nodes(self.nodes);
+            self.nodes = 0;
+        }
+        !self.shared.stopped.load(Ordering::Relaxed) && !(self.cancelled)()
+    }
+}
+
+// Whether a move is worth trying after the last one
+// Turning the same face twice in a row is the same as one turn, and opposite faces don't affect
+// each other so they are only tried in one order
+fn follows(last: Option<&usize>, m: usize) -> bool {
+    let Some(last) = last else {
+        return true;
+    };
+    let (face, last_face) = (m / 3, last / 3);
+    face != last_face && !(face / 2 == last_face / 2 && face < last_face)
+}
+
+// Makes sure the pieces could have come from turning a real cube
+// Every piece has to be there once, corners twist in thirds and edges flip in pairs, and every
+// turn swaps corners and edges an odd number of times together
+fn check_solvable(cubies: &CubieCube) -> Result<(), CubeError> {
+    let mut corners = cubies.corner_permutation;
+    let mut edges = cubies.edge_permutation;
+    corners.sort();
+    edges.sort();
+    if corners != CubieCube::SOLVED.corner_permutation
+        || edges != CubieCube::SOLVED.edge_permutation
+    {
+        return Err(CubeError::InvalidState(
+            "a piece shows up twice".to_string(),
+        ));
+    }
+    let twist: u32 = cubies
+        .corner_orientation
+        .iter()
+        .map(|t| u32::from(*t))
+        .sum();
+    if !twist.is_multiple_of(3) {
+        return Err(CubeError::InvalidState("a corner is twisted".to_string()));
+    }
+    let flip: u32 = cubies.edge_orientation.iter().map(|f| u32::from(*f)).sum();
+    if !flip.is_multiple_of(2) {
+        return Err(CubeError::InvalidState("an edge is flipped".to_string()));
+    }
+    if is_odd(&cubies.corner_permutation) != is_odd(&cubies.edge_permutation) {
+        return Err(CubeError::InvalidState(
+            "two pieces are swapped".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+fn is_odd(permutation: &[u8]) -> bool {
+    let mut odd = false;
+    for i in 0..permutation.len() {
+        for j in i + 1..permutation.len() {
+            if permutation[i] > permutation[j] {
+                odd = !odd;
+            }
+        }
+    }
+    odd
+}
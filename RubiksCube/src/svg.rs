@@ -0,0 +1,319 @@
+use std::fmt::Write;
+
+use crate::geometry::face_point;
+use crate::net::ColorScheme;
+use crate::{read_2d_vec, RubiksColor, RubiksCube, StickerId};
+
+// Drawing a cube as an SVG image, either unfolded flat, as a 3D view of the up, front and right
+// faces, or looking straight down at the last layer. Every face is drawn as a dark square with the
+// stickers set into it, so the gap between stickers shows up as the cube's body
+
+// Where each face goes in the flat net, as a column and row of faces
+const NET_LAYOUT: [(RubiksColor, f64, f64); 6] = [
+    (RubiksColor::UP, 1.0, 0.0),
+    (RubiksColor::LEFT, 0.0, 1.0),
+    (RubiksColor::FORWARD, 1.0, 1.0),
+    (RubiksColor::RIGHT, 2.0, 1.0),
+    (RubiksColor::BACK, 3.0, 1.0),
+    (RubiksColor::DOWN, 1.0, 2.0),
+];
+
+// The faces that can be seen from above, in front and to the right
+const ISOMETRIC_FACES: [RubiksColor; 3] =
+    [RubiksColor::UP, RubiksColor::FORWARD, RubiksColor::RIGHT];
+
+// The faces seen looking down on the last layer, the up face and the top row of the sides
+const LAST_LAYER_FACES: [RubiksColor; 5] = [
+    RubiksColor::UP,
+    RubiksColor::BACK,
+    RubiksColor::LEFT,
+    RubiksColor::RIGHT,
+    RubiksColor::FORWARD,
+];
+
+// The space between faces of the net and around the edge of the picture, in stickers
+const FACE_SPACING: f64 = 0.25;
+const MARGIN: f64 = 0.25;
+
+// How far the side strips of the last layer view are from the up face and how deep they are,
+// in stickers
+const STRIP_SPACING: f64 = 0.1;
+const STRIP_DEPTH: f64 = 0.3;
+
+// How thick the outline around a highlighted sticker is, in stickers
+const HIGHLIGHT_WIDTH: f64 = 0.1;
+
+// The size of arrows, in stickers
+const ARROW_WIDTH: f64 = 0.08;
+const ARROW_HEAD_LENGTH: f64 = 0.3;
+const ARROW_HEAD_WIDTH: f64 = 0.25;
+const ARROW_SHORTENING: f64 = 0.2; // How far short of the middle of each sticker arrows stop
+
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
+pub(crate) enum SvgView {
+    Net,       // All six faces unfolded into a cross
+    Isometric, // The up, front and right faces of the cube in 3D
+    LastLayer, // Looking down on the up face, with the top row of each side around it
+}
+
+#[derive(Clone, Debug)]
+pub(crate) struct SvgOptions {
+    pub(crate) view: SvgView,
+    pub(crate) scheme: ColorScheme,
+    pub(crate) sticker_size: f64, // How wide a sticker is, in pixels
+    pub(crate) gap: f64,          // How much of each sticker's square is left as body around it
+    pub(crate) body: [u8; 3],     // The color between the stickers
+    pub(crate) highlight: Vec<StickerId>, // Stickers drawn with an outline around them
+    pub(crate) highlight_color: [u8; 3],
+    pub(crate) mask: Vec<StickerId>, // Stickers drawn without their color
+    pub(crate) mask_color: [u8; 3],
+    pub(crate) arrows: Vec<(StickerId, StickerId)>, // Drawn from the middle of one to the other
+    pub(crate) arrow_color: [u8; 3],
+}
+
+impl Default for SvgOptions {
+    fn default() -> Self {
+        SvgOptions {
+            view: SvgView::Net,
+            scheme: ColorScheme::default(),
+            sticker_size: 20.0,
+            gap: 0.1,
+            body: [24, 24, 24],
+            highlight: vec![],
+            highlight_color: [255, 0, 255],
+            mask: vec![],
+            mask_color: [128, 128, 128],
+            arrows: vec![],
+            arrow_color: [0, 0, 0],
+        }
+    }
+}
+
+// A filled shape, with its corners in pixels
+struct Polygon {
+    points: Vec<[f64; 2]>,
+    fill: [u8; 3],
+    outline: Option<[u8; 3]>,
+}
+
+impl RubiksCube {
+    pub(crate) fn to_svg(&self, options: &SvgOptions) -> String {
+        let n = self.face_size;
+        // Each face drawn, and how many of its rows
+        let faces: Vec<(RubiksColor, usize)> = match options.view {
+            SvgView::Net => NET_LAYOUT.iter().map(|(face, _, _)| (*face, n)).collect(),
+            SvgView::Isometric => ISOMETRIC_FACES.iter().map(|face| (*face, n)).collect(),
+            SvgView::LastLayer => LAST_LAYER_FACES
+                .iter()
+                .map(|face| (*face, if *face == RubiksColor::UP { n } else { 1 }))
+                .collect(),
+        };
+        let mut polygons: Vec<Polygon> = faces
+            .into_iter()
+            .flat_map(|(face, rows)| {
+                self.face_polygons(face, rows, options, |u, v| self.point(options, face, u, v))
+            })
+            .collect();
+        for (from, to) in &options.arrows {
+            let middle =
+                |id: &StickerId| self.point(options, id.face, id.x as f64 + 0.5, id.y as f64 + 0.5);
+            polygons.extend(arrow(middle(from), middle(to), options));
+        }
+        write_svg(&polygons, options)
+    }
+
+    // Where a position on a face ends up in the picture, in pixels
+    // Positions go from 0 to n across and down the face, the same way stickers are stored
+    fn point(&self, options: &SvgOptions, face: RubiksColor, u: f64, v: f64) -> [f64; 2] {
+        let n = self.face_size as f64;
+        let size = options.sticker_size;
+        // Points on the cube itself are in doubled units, so a sticker is two across
+        let scale = size / 2.0;
+        let cube_point =
+            |a: f64, b: f64| face_point(face, a as i64, b as i64, self.face_size).map(|c| c as f64);
+        match options.view {
+            SvgView::Net => {
+                let face_width = n * size + FACE_SPACING * size;
+                let (_, column, row) = NET_LAYOUT.iter().find(|(f, _, _)| *f == face).unwrap();
+                [column * face_width + u * size, row * face_width + v * size]
+            }
+            SvgView::Isometric => {
+                // x runs down to the right, z down to the left and y straight up
+                let (across, down) = ((30f64).to_radians().cos(), 0.5);
+                let [x, y, z] = cube_point(2.0 * u - n, n - 2.0 * v);
+                [(x - z) * across * scale, ((x + z) * down - y) * scale]
+            }
+            SvgView::LastLayer if face == RubiksColor::UP => {
+                // Looking down, x runs right and z runs down the picture
+                let [x, _, z] = cube_point(2.0 * u - n, n - 2.0 * v);
+                [x * scale, z * scale]
+            }
+            SvgView::LastLayer => {
+                // Side faces are folded out flat from the top edge, which they share with the up
+                // face, with each row pushed out away from the middle
+                let [x, _, z] = cube_point(2.0 * u - n, n);
+                let [out_x, _, out_z] = cube_point(0.0, 0.0).map(|c| c / n);
+                let out = 2.0 * (STRIP_SPACING + v * STRIP_DEPTH);
+                [(x + out_x * out) * scale, (z + out_z * out) * scale]
+            }
+        }
+    }
+
+    // The body of a face and the stickers in its first few rows, with corner turning a position
+    // on the face into pixels
+    fn face_polygons(
+        &self,
+        face: RubiksColor,
+        rows: usize,
+        options: &SvgOptions,
+        corner: impl Fn(f64, f64) -> [f64; 2],
+    ) -> Vec<Polygon> {
+        let n = self.face_size;
+        let (width, height) = (n as f64, rows as f64);
+        let mut polygons = vec![Polygon {
+            points: vec![
+                corner(0.0, 0.0),
+                corner(width, 0.0),
+                corner(width, height),
+                corner(0.0, height),
+            ],
+            fill: options.body,
+            outline: None,
+        }];
+        for y in 0..rows {
+            for x in 0..n {
+                let Some(color) = read_2d_vec(self.face(face), &n, &x, &y) else {
+                    continue;
+                };
+                let id = StickerId { face, x, y };
+                let (left, top) = (x as f64, y as f64);
+                let cell = [
+                    corner(left, top),
+                    corner(left + 1.0, top),
+                    corner(left + 1.0, top + 1.0),
+                    corner(left, top + 1.0),
+                ];
+                // Pulling the corners in towards the middle leaves the gap around the sticker
+                let middle = corner(left + 0.5, top + 0.5);
+                let points = cell
+                    .iter()
+                    .map(|point| {
+                        [0, 1].map(|i| middle[i] + (point[i] - middle[i]) * (1.0 - options.gap))
+                    })
+                    .collect();
+                polygons.push(Polygon {
+                    points,
+                    fill: if options.mask.contains(&id) {
+                        options.mask_color
+                    } else {
+                        options.scheme.rgb(*color)
+                    },
+                    outline: options
+                        .highlight
+                        .contains(&id)
+                        .then_some(options.highlight_color),
+                });
+            }
+        }
+        polygons
+    }
+}
+
+// An arrow from one point to another, as a shaft and a head
+fn arrow(from: [f64; 2], to: [f64; 2], options: &SvgOptions) -> Vec<Polygon> {
+    let size = options.sticker_size;
+    let length = ((to[0] - from[0]).powi(2) + (to[1] - from[1]).powi(2)).sqrt();
+    if length <= 2.0 * ARROW_SHORTENING * size {
+        return vec![];
+    }
+    // Along the arrow, and across it
+    let along = [(to[0] - from[0]) / length, (to[1] - from[1]) / length];
+    let across = [-along[1], along[0]];
+    let at = |distance: f64, side: f64| {
+        [0, 1].map(|i| from[i] + along[i] * distance * size + across[i] * side * size)
+    };
+    let (start, end) = (ARROW_SHORTENING, length / size - ARROW_SHORTENING);
+    let head = end - ARROW_HEAD_LENGTH;
+    let polygon = |points| Polygon {
+        points,
+        fill: options.arrow_color,
+        outline: None,
+    };
+    vec![
+        polygon(vec![
+            at(start, -ARROW_WIDTH / 2.0),
+            at(head, -ARROW_WIDTH / 2.0),
+            at(head, ARROW_WIDTH / 2.0),
+            at(start, ARROW_WIDTH / 2.0),
+        ]),
+        polygon(vec![
+            at(head, -ARROW_HEAD_WIDTH / 2.0),
+            at(end, 0.0),
+            at(head, ARROW_HEAD_WIDTH / 2.0),
+        ]),
+    ]
+}
+
+fn write_svg(polygons: &[Polygon], options: &SvgOptions) -> String {
+    // Fit the picture around everything drawn, with a margin
+    let margin = MARGIN * options.sticker_size;
+    let (mut min, mut max) = ([f64::MAX; 2], [f64::MIN; 2]);
+    for point in polygons.iter().flat_map(|polygon| &polygon.points) {
+        for i in 0..2 {
+            min[i] = min[i].min(point[i] - margin);
+            max[i] = max[i].max(point[i] + margin);
+        }
+    }
+    let (width, height) = (max[0] - min[0], max[1] - min[1]);
+    let mut svg = String::new();
+    // Writing to a String can't fail
+    let _ = writeln!(
+        svg,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{}" height="{}" viewBox="{} {} {} {}">"#,
+        number(width),
+        number(height),
+        number(min[0]),
+        number(min[1]),
+        number(width),
+        number(height)
+    );
+    for polygon in polygons {
+        let points: Vec<String> = polygon
+            .points
+            .iter()
+            .map(|[x, y]| format!("{},{}", number(*x), number(*y)))
+            .collect();
+        let _ = write!(
+            svg,
+            r#"<polygon points="{}" fill="{}""#,
+            points.join(" "),
+            hex(polygon.fill)
+        );
+        if let Some(outline) = polygon.outline {
+            let _ = write!(
+                svg,
+                r#" stroke="{}" stroke-width="{}" stroke-linejoin="round""#,
+                hex(outline),
+                number(HIGHLIGHT_WIDTH * options.sticker_size)
+            );
+        }
+        let _ = writeln!(svg, "/>");
+    }
+    svg.push_str("</svg>\n");
+    svg
+}
+
+pub(crate) fn hex([r, g, b]: [u8; 3]) -> String {
+    format!("#{r:02x}{g:02x}{b:02x}")
+}
+
+// Pixel positions to two decimal places, without trailing zeros to keep the file small
+pub(crate) fn number(value: f64) -> String {
+    let text = format!("{value:.2}");
+    let text = text.trim_end_matches('0').trim_end_matches('.');
+    if text == "-0" {
+        "0".to_string()
+    } else {
+        text.to_string()
+    }
+}
@@ -0,0 +1,195 @@
+use std::cmp::Ordering;
+
+use crate::geometry::{point_sticker, sticker_point};
+use crate::notation::Move;
+use crate::{RubiksColor, RubiksCube, Sticker, StickerId};
+
+// Code for the ways the whole cube can be rotated or mirrored onto itself
+// Turning a cube state into another one by a symmetry means rotating or mirroring everything,
+// stickers and colors alike, so two states related that way are solved the same way and search
+// tables only need to know about one of them
+
+// A rotation or reflection of the whole cube, written as where each axis ends up
+// The i'th coordinate of a moved point is the axes[i]'th coordinate of the original, negated if
+// flips[i] is set
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
+pub(crate) struct Symmetry {
+    axes: [usize; 3],
+    flips: [bool; 3],
+}
+
+impl Symmetry {
+    pub(crate) const IDENTITY: Symmetry = Symmetry {
+        axes: [0, 1, 2],
+        flips: [false; 3],
+    };
+
+    // All 48 symmetries, the 24 rotations first with the identity at the front
+    pub(crate) fn all() -> Vec<Symmetry> {
+        let orders = [
+            [0, 1, 2],
+            [1, 2, 0],
+            [2, 0, 1],
+            [0, 2, 1],
+            [2, 1, 0],
+            [1, 0, 2],
+        ];
+        let mut symmetries = vec![];
+        for axes in orders {
+            for signs in 0..8 {
+                let flips = [signs & 1 != 0, signs & 2 != 0, signs & 4 != 0];
+                symmetries.push(Symmetry { axes, flips });
+            }
+        }
+        symmetries.sort_by_key(|symmetry| !symmetry.is_rotation());
+        symmetries
+    }
+
+    // The 24 ways of picking the cube up and putting it back down
+    pub(crate) fn rotations() -> Vec<Symmetry> {
+        Self::all().into_iter().filter(Self::is_rotation).collect()
+    }
+
+    // Reflections turn the cube inside out, which swaps clockwise and counterclockwise
+    pub(crate) fn is_rotation(&self) -> bool {
+        // Swapping two axes mirrors the cube, as does flipping one
+        let swaps = match self.axes {
+            [0, 1, 2] | [1, 2, 0] | [2, 0, 1] => 0,
+            _ => 1,
+        };
+        let flips = self.flips.iter().filter(|flip| **flip).count();
+        (swaps + flips) % 2 == 0
+    }
+
+    pub(crate) fn apply_point(&self, point: [i64; 3]) -> [i64; 3] {
+        [0, 1, 2].map(|i| {
+            let value = point[self.axes[i]];
+            if self.flips[i] {
+                -value
+            } else {
+                value
+            }
+        })
+    }
+
+    // The symmetry that puts everything back where this one found it
+    pub(crate) fn inverse(&self) -> Symmetry {
+        let mut inverse = Symmetry::IDENTITY;
+        for i in 0..3 {
+            inverse.axes[self.axes[i]] = i;
+            inverse.flips[self.axes[i]] = self.flips[i];
+        }
+        inverse
+    }
+
+    // Doing other and then this symmetry
+    pub(crate) fn after(&self, other: &Symmetry) -> Symmetry {
+        Symmetry {
+            axes: self.axes.map(|axis| other.axes[axis]),
+            flips: [0, 1, 2].map(|i| self.flips[i] != other.flips[self.axes[i]]),
+        }
+    }
+
+    // Where the sticker at a position ends up
+    pub(crate) fn apply_position(
+        &self,
+        face: RubiksColor,
+        x: usize,
+        y: usize,
+        face_size: usize,
+    ) -> (RubiksColor, usize, usize) {
+        point_sticker(
+            self.apply_point(sticker_point(face, x, y, face_size)),
+            face_size,
+        )
+    }
+
+    // Which face the given face is carried onto, and so how colors get relabelled
+    pub(crate) fn apply_color(&self, color: RubiksColor) -> RubiksColor {
+        self.apply_position(color, 0, 0, 1).0
+    }
+
+    // The move that does to a transformed cube what the given move does to the original
+    // Mirrors see every turn the other way around
+    pub(crate) fn apply_move(&self, m: &Move) -> Move {
+        let direction = if self.is_rotation() {
+            m.direction
+        } else {
+            m.direction.opposite()
+        };
+        Move {
+            face: self.apply_color(m.face),
+            direction,
+            ..*m
+        }
+    }
+}
+
+// Stickers that can be carried along when the whole cube is rotated or mirrored
+pub(crate) trait SymmetricSticker: Sticker {
+    fn transformed(self, symmetry: &Symmetry, face_size: usize) -> Self;
+}
+
+impl SymmetricSticker for RubiksColor {
+    fn transformed(self, symmetry: &Symmetry, _face_size: usize) -> Self {
+        symmetry.apply_color(self)
+    }
+}
+
+impl SymmetricSticker for StickerId {
+    fn transformed(self, symmetry: &Symmetry, face_size: usize) -> Self {
+        let (face, x, y) = symmetry.apply_position(self.face, self.x, self.y, face_size);
+        StickerId { face, x, y }
+    }
+}
+
+impl<S: SymmetricSticker> RubiksCube<S> {
+    // Rotates or mirrors the whole state, moving every sticker and relabelling it to match
+    // If this state came from a sequence of moves, the result comes from the same moves rotated or
+    // mirrored the same way, so the symmetry's inverse, then the moves, then the symmetry
+    pub(crate) fn conjugate(&self, symmetry: &Symmetry) -> RubiksCube<S> {
+        let n = self.face_size;
+        let mut stickers = self.stickers.clone();
+        for face in RubiksColor::ALL {
+            for y in 0..n {
+                for x in 0..n {
+                    let (to_face, to_x, to_y) = symmetry.apply_position(face, x, y, n);
+                    stickers[self.sticker_index(to_face, to_x, to_y)] =
+                        self.stickers[self.sticker_index(face, x, y)].transformed(symmetry, n);
+                }
+            }
+        }
+        RubiksCube::from_stickers(n, stickers)
+    }
+
+    // Picks the same state out of every group of states related by symmetry
+    // Returns the chosen state and the symmetry that turns this state into it
+    pub(crate) fn canonical(&self) -> (RubiksCube<S>, Symmetry) {
+        let mut best = (self.clone(), Symmetry::IDENTITY);
+        for symmetry in Symmetry::all().into_iter().skip(1) {
+            let candidate = self.conjugate(&symmetry);
+            if compare_stickers(&candidate, &best.0) == Ordering::Less {
+                best = (candidate, symmetry);
+            }
+        }
+        best
+    }
+
+    // Every symmetry that leaves this state exactly as it is
+    // A solved cube is kept the same by all 48, and the more a scramble has, the fewer distinct
+    // states it stands in for
+    pub(crate) fn stabilizer(&self) -> Vec<Symmetry> {
+        Symmetry::all()
+            .into_iter()
+            .filter(|symmetry| compare_stickers(&self.conjugate(symmetry), self) == Ordering::Equal)
+            .collect()
+    }
+}
+
+// Puts cubes of the same size in a fixed order, sticker by sticker
+fn compare_stickers<S: Sticker>(a: &RubiksCube<S>, b: &RubiksCube<S>) -> Ordering {
+    a.stickers
+        .iter()
+        .map(Sticker::code)
+        .cmp(b.stickers.iter().map(Sticker::code))
+}
@@ -0,0 +1,288 @@
+use std::io::{IsTerminal, Read, Write};
+use std::ops::Range;
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+
+use crate::net::{ColorMode, ColorScheme};
+use crate::notation::Algorithm;
+use crate::scramble::Scrambler;
+use crate::{CubeError, RubiksColor, RubiksCube, TurnDirection};
+
+// A virtual cube filling the terminal, turned straight from the keyboard with the same keys as
+// cstimer's virtual cube
+// The terminal is put in raw mode with stty so every key arrives as soon as it is pressed, and is
+// put back however the program ends. Reads give up after a tenth of a second, so the timer keeps
+// counting while no keys come in
+
+// How long stty waits for a key before a read gives up, in tenths of a second
+const READ_TIMEOUT: &str = "1";
+
+const CTRL_C: u8 = 0x03;
+const CTRL_D: u8 = 0x04;
+const ESCAPE: u8 = 0x1b;
+
+// How many of a face's layers a key turns
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
+enum Turn {
+    Face,     // Just the outside layer, like R
+    Wide,     // The outside two layers, like r
+    Slice,    // Every layer but the outside ones, like M
+    Rotation, // The whole cube, like x
+}
+
+impl Turn {
+    fn layers(&self, face_size: usize) -> Range<usize> {
+        match self {
+            Turn::Face => 0..1,
+            Turn::Wide => 0..face_size.min(2),
+            Turn::Slice => 1..face_size.saturating_sub(1).max(1),
+            Turn::Rotation => 0..face_size,
+        }
+    }
+}
+
+// cstimer's keys, with the right hand on the home row turning R, U and D' and the left hand their
+// opposites, along with E and S on the number row, which cstimer leaves out
+// Slices turn the way the face they are named after does, so M is turned from L
+const KEYS: [(u8, Turn, RubiksColor, TurnDirection); 36] = {
+    use RubiksColor::*;
+    use Turn::*;
+    use TurnDirection::*;
+    [
+        (b'i', Face, RIGHT, CLOCKWISE),
+        (b'k', Face, RIGHT, COUNTERCLOCKWISE),
+        (b'd', Face, LEFT, CLOCKWISE),
+        (b'e', Face, LEFT, COUNTERCLOCKWISE),
+        (b'j', Face, UP, CLOCKWISE),
+        (b'f', Face, UP, COUNTERCLOCKWISE),
+        (b's', Face, DOWN, CLOCKWISE),
+        (b'l', Face, DOWN, COUNTERCLOCKWISE),
+        (b'h', Face, FORWARD, CLOCKWISE),
+        (b'g', Face, FORWARD, COUNTERCLOCKWISE),
+        (b'w', Face, BACK, CLOCKWISE),
+        (b'o', Face, BACK, COUNTERCLOCKWISE),
+        (b'u', Wide, RIGHT, CLOCKWISE),
+        (b'm', Wide, RIGHT, COUNTERCLOCKWISE),
+        (b'v', Wide, LEFT, CLOCKWISE),
+        (b'r', Wide, LEFT, COUNTERCLOCKWISE),
+        (b',', Wide, UP, CLOCKWISE),
+        (b'c', Wide, UP, COUNTERCLOCKWISE),
+        (b'z', Wide, DOWN, CLOCKWISE),
+        (b'/', Wide, DOWN, COUNTERCLOCKWISE),
+        (b'5', Slice, LEFT, CLOCKWISE),
+        (b'6', Slice, LEFT, CLOCKWISE),
+        (b'x', Slice, LEFT, COUNTERCLOCKWISE),
+        (b'.', Slice, LEFT, COUNTERCLOCKWISE),
+        (b'2', Slice, DOWN, CLOCKWISE),
+        (b'3', Slice, DOWN, COUNTERCLOCKWISE),
+        (b'8', Slice, FORWARD, CLOCKWISE),
+        (b'9', Slice, FORWARD, COUNTERCLOCKWISE),
+        (b't', Rotation, RIGHT, CLOCKWISE),
+        (b'y', Rotation, RIGHT, CLOCKWISE),
+        (b'b', Rotation, RIGHT, COUNTERCLOCKWISE),
+        (b'n', Rotation, RIGHT, COUNTERCLOCKWISE),
+        (b';', Rotation, UP, CLOCKWISE),
+        (b'a', Rotation, UP, COUNTERCLOCKWISE),
+        (b'p', Rotation, FORWARD, CLOCKWISE),
+        (b'q', Rotation, FORWARD, COUNTERCLOCKWISE),
+    ]
+};
+
+const HELP: &str = "\
+space new scramble   esc solved cube   ctrl-c quit
+i k R   d e L   j f U   s l D   h g F   w o B   u m r   v r l   , c u   z / d
+5 6 M   x . M'   2 3 E   8 9 S   t y x   b n x'   ; a y   p q z";
+
+// Where the solve is up to
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Timer {
+    Idle,               // Nothing to time, the cube wasn't scrambled
+    Ready,              // Scrambled, starting with the first move
+    Running(Instant),   // Started at this time
+    Finished(Duration), // Solved in this long
+}
+
+// The cube and everything shown around it
+pub(crate) struct Session {
+    pub(crate) cube: RubiksCube,
+    scramble: Option<Algorithm>,
+    pub(crate) moves: usize, // Turns since the scramble, not counting whole cube rotations
+    pub(crate) timer: Timer,
+    scrambler: Scrambler,
+    scheme: ColorScheme,
+    colors: ColorMode,
+}
+
+impl Session {
+    pub(crate) fn new(face_size: usize) -> Result<Self, CubeError> {
+        Ok(Session {
+            cube: RubiksCube::try_new(face_size)?,
+            scramble: None,
+            moves: 0,
+            timer: Timer::Idle,
+            scrambler: Scrambler::new(),
+            scheme: ColorScheme::default(),
+            colors: ColorMode::detect(),
+        })
+    }
+
+    // Acts on one key, returning false once it's time to quit
+    pub(crate) fn press(&mut self, key: u8) -> Result<bool, CubeError> {
+        let n = self.cube.face_size;
+        match key.to_ascii_lowercase() {
+            CTRL_C | CTRL_D => return Ok(false),
+            b' ' => {
+                let scramble = self.scrambler.scramble(n);
+                self.cube = RubiksCube::try_new(n)?.try_do_algorithm(&scramble)?;
+                self.scramble = Some(scramble);
+                self.moves = 0;
+                self.timer = Timer::Ready;
+            }
+            ESCAPE => {
+                self.cube = RubiksCube::try_new(n)?;
+                self.scramble = None;
+                self.moves = 0;
+                self.timer = Timer::Idle;
+            }
+            key => {
+                let Some((_, turn, face, direction)) = KEYS.iter().find(|(k, ..)| *k == key) else {
+                    return Ok(true);
+                };
+                // Cubes smaller than 3x3 have no slices, so those keys do nothing at all
+                if turn.layers(n).is_empty() {
+                    return Ok(true);
+                }
+                for layer in turn.layers(n) {
+                    self.cube.try_apply_move(*face, layer, *direction)?;
+                }
+                if *turn != Turn::Rotation {
+                    self.moves += 1;
+                    if self.timer == Timer::Ready {
+                        self.timer = Timer::Running(Instant::now());
+                    }
+                }
+                if let Timer::Running(start) = self.timer {
+                    if self.solved() {
+                        self.timer = Timer::Finished(start.elapsed());
+                    }
+                }
+            }
+        }
+        Ok(true)
+    }
+
+    // Whether every face is one color, which after whole cube rotations isn't the same as being
+    // back where it started
+    fn solved(&self) -> bool {
+        RubiksColor::ALL.iter().all(|face| {
+            let stickers = self.cube.face(*face);
+            stickers.iter().all(|sticker| *sticker == stickers[0])
+        })
+    }
+
+    // Everything on the screen, with every line ending in \r\n since raw mode doesn't add the \r
+    fn screen(&self) -> String {
+        let net = self.cube.color_net(self.scheme, self.colors).fit_terminal();
+        let scramble = self
+            .scramble
+            .as_ref()
+            .map_or("none, press space".to_string(), Algorithm::to_string);
+        let time = match self.timer {
+            Timer::Idle | Timer::Ready => "0.00".to_string(),
+            Timer::Running(start) => format!("{:.2}", start.elapsed().as_secs_f64()),
+            Timer::Finished(time) => format!("{:.2} solved!", time.as_secs_f64()),
+        };
+        let text = format!(
+            "{net}\n\nScramble: {scramble}\n\nMoves: {}   Time: {time}\n\n{HELP}",
+            self.moves
+        );
+        // Clearing the end of each line as it's drawn again stops the screen flickering
+        let mut screen = "\x1b[H".to_string();
+        for line in text.lines() {
+            screen.push_str(line);
+            screen.push_str("\x1b[K\r\n");
+        }
+        screen.push_str("\x1b[J");
+        screen
+    }
+}
+
+// Holds the terminal in raw mode on the alternate screen, putting it back when dropped
+struct RawTerminal {
+    saved: String, // The settings from before, as stty -g prints them
+}
+
+impl RawTerminal {
+    fn enter() -> Result<Self, CubeError> {
+        let saved = stty(&["-g"])?;
+        stty(&["raw", "-echo", "min", "0", "time", READ_TIMEOUT])?;
+        // The alternate screen keeps whatever was on the terminal, and the cursor is hidden
+        print!("\x1b[?1049h\x1b[?25l\x1b[2J");
+        std::io::stdout().flush()?;
+        Ok(RawTerminal {
+            saved: saved.trim().to_string(),
+        })
+    }
+}
+
+impl Drop for RawTerminal {
+    fn drop(&mut self) {
+        print!("\x1b[?25h\x1b[?1049l");
+        let _ = std::io::stdout().flush();
+        let _ = stty(&[&self.saved]);
+    }
+}
+
+// Runs stty on the terminal standard input is connected to, returning what it prints
+fn stty(args: &[&str]) -> Result<String, CubeError> {
+    let output = Command::new("stty")
+        .args(args)
+        .stdin(Stdio::inherit())
+        .output()?;
+    if !output.status.success() {
+        return Err(CubeError::Io(format!(
+            "stty {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+// Runs the virtual cube until ctrl-c or ctrl-d
+pub(crate) fn run(face_size: usize) -> Result<(), CubeError> {
+    let mut session = Session::new(face_size)?;
+    if !std::io::stdin().is_terminal() || !std::io::stdout().is_terminal() {
+        return Err(CubeError::Io(
+            "the virtual cube needs to be run in a terminal".to_string(),
+        ));
+    }
+    let _terminal = RawTerminal::enter()?;
+    let mut stdin = std::io::stdin().lock();
+    let mut stdout = std::io::stdout().lock();
+    let mut keys = [0u8; 64];
+    loop {
+        stdout.write_all(session.screen().as_bytes())?;
+        stdout.flush()?;
+        let count = stdin.read(&mut keys)?;
+        let mut pressed = keys[..count].iter().copied();
+        while let Some(key) = pressed.next() {
+            // Arrow keys and the like come as escape followed by [ or O and more, and are skipped
+            // so only a lone escape resets the cube
+            if key == ESCAPE {
+                if let Some(b'[' | b'O') = pressed.clone().next() {
+                    pressed.next();
+                    for rest in pressed.by_ref() {
+                        if rest.is_ascii_alphabetic() || rest == b'~' {
+                            break;
+                        }
+                    }
+                    continue;
+                }
+            }
+            if !session.press(key)? {
+                return Ok(());
+            }
+        }
+    }
+}